@@ -0,0 +1,210 @@
+use {
+  clap::{CommandFactory, Parser, error::ErrorKind},
+  flux::{
+    export::{Exporter, Format, SurfaceFormat},
+    harness::CaptureLevel,
+    prelude::*,
+    scene::Scene,
+    stand::Stand,
+    surface::Reconstruction,
+  },
+  std::{fmt::Display, path::PathBuf},
+};
+
+/// Runs a fluid stand, either in a window or headless.
+#[derive(Parser, Debug, Clone)]
+#[command(version, about)]
+pub struct Args {
+  /// Scene file to simulate.
+  #[arg(long, default_value = "scenes/default.ron")]
+  pub scene: PathBuf,
+
+  /// Plays back a recorded timeline instead of simulating.
+  #[arg(long, conflicts_with_all = ["headless", "record", "max_steps"])]
+  pub replay: Option<PathBuf>,
+
+  /// Overrides the time step of the scene, in seconds.
+  #[arg(long, value_parser = positive)]
+  pub dt: Option<Real>,
+
+  /// Overrides the particle radius of the scene.
+  #[arg(long, value_parser = positive)]
+  pub particle_radius: Option<Real>,
+
+  /// Overrides the smoothing factor of the scene.
+  #[arg(long, value_parser = positive)]
+  pub smoothing_factor: Option<Real>,
+
+  /// Amount of threads used by the physics and fluid solvers.
+  #[arg(long, value_parser = clap::value_parser!(u16).range(1..))]
+  pub threads: Option<u16>,
+
+  /// Stops simulating after this amount of steps.
+  #[arg(long)]
+  pub max_steps: Option<usize>,
+
+  /// Runs without a window, requires `--max-steps`.
+  #[arg(long, requires = "max_steps")]
+  pub headless: bool,
+
+  /// Logs the progress of a headless run every this amount of steps.
+  #[arg(long, default_value_t = 100)]
+  pub progress: usize,
+
+  /// Overrides how much of the fluids is recorded: kinematics,
+  /// accelerations or solver.
+  #[arg(long)]
+  pub capture: Option<CaptureLevel>,
+
+  /// Saves the recorded timeline to this file when the run ends.
+  #[arg(long)]
+  pub record: Option<PathBuf>,
+
+  /// Writes the time series of every probe of the scene as `<name>.csv`
+  /// into this directory when the run ends.
+  #[arg(long, conflicts_with = "replay")]
+  pub probes: Option<PathBuf>,
+
+  /// Only records every this amount of steps.
+  #[arg(
+    long,
+    default_value_t = 1,
+    value_parser = clap::value_parser!(u32).range(1..),
+  )]
+  pub decimation: u32,
+
+  /// Writes every recorded step as particle files into this directory, with
+  /// `--replay` the timeline is converted without opening a window.
+  #[arg(long)]
+  pub export: Option<PathBuf>,
+
+  /// File format of `--export`: vtk, vtk-binary, vtp, ply or ply-binary.
+  #[arg(long, default_value_t = Format::Vtk, requires = "export")]
+  pub export_format: Format,
+
+  /// Also exports the reconstructed surface of every fluid as meshes: obj,
+  /// ply or ply-binary.
+  #[arg(long, requires = "export")]
+  pub export_surfaces: Option<SurfaceFormat>,
+
+  /// Overrides the edge of the surface reconstruction cells, relative to
+  /// the particle radius.
+  #[arg(long, value_parser = positive)]
+  pub surface_cell: Option<Real>,
+
+  /// Overrides the radius of the surface reconstruction kernel, relative to
+  /// the particle radius.
+  #[arg(long, value_parser = positive)]
+  pub surface_kernel: Option<Real>,
+
+  /// Overrides the level of the colour field on the reconstructed surfaces.
+  #[arg(long, value_parser = positive)]
+  pub surface_iso: Option<Real>,
+}
+
+fn positive(arg: &str) -> Result<Real, String> {
+  match arg.parse::<Real>() {
+    Ok(value) if value > 0.0 && value.is_finite() => Ok(value),
+    Ok(_) => Err("must be a positive number".into()),
+    Err(err) => Err(err.to_string()),
+  }
+}
+
+/// Reports `err` the same way as invalid arguments and exits.
+pub fn exit(kind: ErrorKind, err: impl Display) -> ! {
+  Args::command().error(kind, err).exit()
+}
+
+impl Args {
+  pub fn parse() -> Self {
+    let args = <Self as Parser>::parse();
+    if cfg!(not(feature = "parallel")) && args.threads.is_some_and(|n| n > 1) {
+      exit(
+        ErrorKind::ArgumentConflict,
+        "`--threads` requires the `parallel` feature",
+      );
+    }
+    args
+  }
+
+  /// Loads the scene with the overrides applied.
+  pub fn scene(&self) -> Scene {
+    let mut scene = Scene::load(&self.scene).unwrap_or_else(|err| {
+      exit(ErrorKind::Io, format!("{}: {err}", self.scene.display()))
+    });
+    self.apply(&mut scene);
+    scene
+  }
+
+  /// Overrides the scene parameters given on the command line.
+  pub fn apply(&self, scene: &mut Scene) {
+    if let Some(dt) = self.dt {
+      scene.integration.dt = dt;
+    }
+    if let Some(radius) = self.particle_radius {
+      scene.particle_radius = radius;
+    }
+    if let Some(factor) = self.smoothing_factor {
+      scene.smoothing_factor = factor;
+    }
+    if let Some(level) = self.capture {
+      scene.capture = level;
+    }
+    self.apply_surface(&mut scene.surface);
+  }
+
+  fn apply_surface(&self, reconstruction: &mut Reconstruction) {
+    if let Some(cell) = self.surface_cell {
+      reconstruction.cell = cell;
+    }
+    if let Some(kernel) = self.surface_kernel {
+      reconstruction.kernel = kernel;
+    }
+    if let Some(iso) = self.surface_iso {
+      reconstruction.iso = iso;
+    }
+  }
+
+  /// The surface reconstruction of the scene, or the default one when
+  /// replaying, with the overrides applied.
+  pub fn reconstruction(&self) -> Reconstruction {
+    if self.replay.is_none() {
+      return self.scene().surface;
+    }
+    let mut reconstruction = Reconstruction::default();
+    self.apply_surface(&mut reconstruction);
+    reconstruction
+  }
+
+  pub fn stand(&self) -> Stand {
+    let mut stand = self.scene().build().unwrap_or_else(|err| {
+      exit(ErrorKind::InvalidValue, format!("{}: {err}", self.scene.display()))
+    });
+
+    #[cfg(feature = "parallel")]
+    if let Some(threads) = self.threads {
+      stand.harness.state.set_num_threads(threads.into());
+    }
+    stand.max_steps = self.max_steps;
+    stand
+  }
+
+  pub fn exporter(&self) -> Option<Exporter> {
+    let dir = self.export.as_ref()?;
+    let exporter =
+      Exporter::new(dir, self.export_format).unwrap_or_else(|err| {
+        exit(ErrorKind::Io, format!("{}: {err}", dir.display()))
+      });
+    Some(match self.export_surfaces {
+      Some(format) => exporter.with_surfaces(self.reconstruction(), format),
+      None => exporter,
+    })
+  }
+
+  pub fn timeline(&self) -> stand::TimelineConfig {
+    stand::TimelineConfig {
+      decimation: self.decimation as usize,
+      ..Default::default()
+    }
+  }
+}
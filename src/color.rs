@@ -0,0 +1,300 @@
+use {
+  crate::{
+    harness::FluidsSnapshot,
+    prelude::*,
+    sph::{CubicSpline, Grid},
+  },
+  rapier::math::Vector,
+};
+
+/// The particle quantity mapped onto the [`Colormap`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorMode {
+  #[default]
+  Speed,
+  /// SPH density relative to the rest density.
+  Density,
+  /// Compression of the particles, zero at rest density and below.
+  Pressure,
+  /// Index of the fluid the particle belongs to.
+  Fluid,
+  /// Amount of fluid and boundary particles within the kernel radius.
+  Neighbours,
+  /// Magnitude of the curl of the velocity.
+  Vorticity,
+  Height,
+}
+
+impl ColorMode {
+  pub const ALL: [Self; 7] = [
+    Self::Speed,
+    Self::Density,
+    Self::Pressure,
+    Self::Fluid,
+    Self::Neighbours,
+    Self::Vorticity,
+    Self::Height,
+  ];
+
+  pub fn next(self) -> Self {
+    next(&Self::ALL, self)
+  }
+
+  pub fn label(self) -> &'static str {
+    match self {
+      Self::Speed => "speed",
+      Self::Density => "density",
+      Self::Pressure => "pressure",
+      Self::Fluid => "fluid",
+      Self::Neighbours => "neighbours",
+      Self::Vorticity => "vorticity",
+      Self::Height => "height",
+    }
+  }
+
+  fn needs_neighbours(self) -> bool {
+    matches!(
+      self,
+      Self::Density | Self::Pressure | Self::Neighbours | Self::Vorticity
+    )
+  }
+}
+
+/// Maps values in `0..=1` to colors.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Colormap {
+  /// The historical blue to pink ramp of the stand.
+  #[default]
+  Classic,
+  Viridis,
+  Magma,
+  Coolwarm,
+  Grayscale,
+}
+
+impl Colormap {
+  pub const ALL: [Self; 5] = [
+    Self::Classic,
+    Self::Viridis,
+    Self::Magma,
+    Self::Coolwarm,
+    Self::Grayscale,
+  ];
+
+  pub fn next(self) -> Self {
+    next(&Self::ALL, self)
+  }
+
+  pub fn label(self) -> &'static str {
+    match self {
+      Self::Classic => "classic",
+      Self::Viridis => "viridis",
+      Self::Magma => "magma",
+      Self::Coolwarm => "coolwarm",
+      Self::Grayscale => "grayscale",
+    }
+  }
+
+  /// Evenly spaced sRGB stops, interpolated linearly.
+  fn stops(self) -> &'static [[u8; 3]] {
+    match self {
+      Self::Classic => &[[0, 51, 166], [255, 128, 217]],
+      Self::Viridis => &[
+        [68, 1, 84],
+        [72, 40, 120],
+        [62, 73, 137],
+        [49, 104, 142],
+        [38, 130, 142],
+        [31, 158, 137],
+        [53, 183, 121],
+        [110, 206, 88],
+        [181, 222, 43],
+        [253, 231, 37],
+      ],
+      Self::Magma => &[
+        [0, 0, 4],
+        [28, 16, 68],
+        [79, 18, 123],
+        [129, 37, 129],
+        [181, 54, 122],
+        [229, 80, 100],
+        [251, 135, 97],
+        [254, 194, 135],
+        [252, 253, 191],
+      ],
+      Self::Coolwarm => &[
+        [59, 76, 192],
+        [124, 159, 249],
+        [192, 212, 245],
+        [221, 221, 221],
+        [242, 203, 183],
+        [238, 133, 105],
+        [180, 4, 38],
+      ],
+      Self::Grayscale => &[[0, 0, 0], [255, 255, 255]],
+    }
+  }
+
+  /// The color at `t`, clamped into `0..=1`.
+  pub fn sample(self, t: Real) -> Color {
+    let stops = self.stops();
+    let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+    let x = t * (stops.len() - 1) as Real;
+    let i = (x.floor() as usize).min(stops.len() - 2);
+    let f = x - i as Real;
+    let [r, g, b] = [0, 1, 2].map(|c| {
+      let (a, b) = (stops[i][c] as Real, stops[i + 1][c] as Real);
+      (a + (b - a) * f) / 255.0
+    });
+    Color::srgb(r, g, b)
+  }
+}
+
+fn next<T: Copy + PartialEq>(all: &[T], current: T) -> T {
+  let i = all.iter().position(|&x| x == current).unwrap_or_default();
+  all[(i + 1) % all.len()]
+}
+
+/// The values mapped onto the ends of the colormap.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Range {
+  /// From the minimum to the maximum over every fluid of the drawn frame.
+  #[default]
+  Frame,
+  Fixed(Real, Real),
+}
+
+/// How the particles of a [`FluidsSnapshot`] are colored.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub struct Coloring {
+  pub mode: ColorMode,
+  pub colormap: Colormap,
+  pub range: Range,
+}
+
+impl Coloring {
+  /// One value per particle of each fluid of `snapshot`, preferring the
+  /// fields captured by the solver over estimating them.
+  pub fn values(&self, snapshot: &FluidsSnapshot) -> Vec<Vec<Real>> {
+    if let Some(values) = self.captured(snapshot) {
+      return values;
+    }
+
+    let fluids = &snapshot.fluids;
+    if !self.mode.needs_neighbours() {
+      return fluids
+        .iter()
+        .enumerate()
+        .map(|(index, (_, fluid))| {
+          let value = |i: usize| match self.mode {
+            ColorMode::Fluid => index as Real,
+            ColorMode::Height => fluid.positions[i].y,
+            _ => fluid.velocities[i].norm(),
+          };
+          (0..fluid.positions.len()).map(value).collect()
+        })
+        .collect();
+    }
+
+    // Fluids interact with each other and with the boundaries, so neighbours
+    // are searched in all of them at once, boundaries last. Boundaries are
+    // sampled at the particle spacing, so they weigh like fluid particles.
+    let mut points: Vec<_> = fluids
+      .iter()
+      .flat_map(|(_, fluid)| fluid.positions.iter().copied())
+      .collect();
+    let count = points.len();
+    points.extend(
+      snapshot
+        .boundaries
+        .iter()
+        .flat_map(|(_, boundary)| boundary.positions.iter().copied()),
+    );
+    let velocities: Vec<_> = fluids
+      .iter()
+      .flat_map(|(_, fluid)| fluid.velocities.iter().copied())
+      .collect();
+
+    let diameter = 2.0 * snapshot.particle_radius;
+    let kernel = CubicSpline::new(diameter * snapshot.smoothing_factor);
+    let volume = diameter.powi(3);
+    let grid = Grid::new(&points, kernel.h);
+
+    let value = |i: usize| {
+      let neighbours = grid.neighbours(&points[i]);
+      match self.mode {
+        ColorMode::Neighbours => neighbours.count() as Real - 1.0,
+        ColorMode::Vorticity => neighbours
+          .filter(|&j| j < count)
+          .map(|j| {
+            let gradient = kernel.gradient(&(points[i] - points[j]));
+            (velocities[j] - velocities[i]).cross(&gradient) * volume
+          })
+          .sum::<Vector<Real>>()
+          .norm(),
+        mode => {
+          let density: Real = neighbours
+            .map(|j| {
+              volume * kernel.value(na::distance(&points[i], &points[j]))
+            })
+            .sum();
+          if mode == ColorMode::Pressure {
+            (density - 1.0).max(0.0)
+          } else {
+            density
+          }
+        }
+      }
+    };
+
+    let mut values = (0..count).map(value);
+    fluids
+      .iter()
+      .map(|(_, fluid)| values.by_ref().take(fluid.positions.len()).collect())
+      .collect()
+  }
+
+  /// The values of the mode recorded in every fluid of `snapshot`.
+  fn captured(&self, snapshot: &FluidsSnapshot) -> Option<Vec<Vec<Real>>> {
+    snapshot
+      .fluids
+      .iter()
+      .map(|(_, fluid)| {
+        let fields = &fluid.fields;
+        let values: Vec<_> = match self.mode {
+          ColorMode::Density => {
+            fields.densities.iter().map(|d| d / fluid.density0).collect()
+          }
+          ColorMode::Pressure => fields.density_error.clone(),
+          ColorMode::Neighbours => {
+            fields.neighbours.iter().map(|&n| n as Real).collect()
+          }
+          _ => return None,
+        };
+        (values.len() == fluid.positions.len()).then_some(values)
+      })
+      .collect()
+  }
+
+  /// The values mapped onto the ends of the colormap.
+  pub fn bounds(&self, values: &[Vec<Real>]) -> (Real, Real) {
+    match self.range {
+      Range::Fixed(min, max) => (min, max),
+      Range::Frame => values
+        .iter()
+        .flatten()
+        .filter(|value| value.is_finite())
+        .fold(None, |bounds, &value| match bounds {
+          None => Some((value, value)),
+          Some((min, max)) => Some((value.min(min), value.max(max))),
+        })
+        .unwrap_or((0.0, 1.0)),
+    }
+  }
+
+  pub fn color(&self, value: Real, (min, max): (Real, Real)) -> Color {
+    let span = max - min;
+    let t = if span > Real::EPSILON { (value - min) / span } else { 0.5 };
+    self.colormap.sample(t)
+  }
+}
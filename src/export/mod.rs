@@ -0,0 +1,384 @@
+mod obj;
+mod ply;
+mod vtk;
+mod vtp;
+
+use {
+  crate::{
+    harness::{Fields, FluidsSnapshot},
+    mesh::Mesh,
+    prelude::*,
+    snapshot::PhysicsSnapshot,
+    stand::{Frame, Timeline},
+    surface::Reconstruction,
+  },
+  std::{
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    iter,
+    path::{Path, PathBuf},
+    str::FromStr,
+  },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+  /// Legacy VTK polydata, ASCII.
+  #[default]
+  Vtk,
+  /// Legacy VTK polydata, big-endian binary.
+  VtkBinary,
+  Ply,
+  /// PLY, little-endian binary.
+  PlyBinary,
+  /// VTK XML polydata, ASCII.
+  Vtp,
+}
+
+impl Format {
+  pub fn extension(self) -> &'static str {
+    match self {
+      Format::Vtk | Format::VtkBinary => "vtk",
+      Format::Ply | Format::PlyBinary => "ply",
+      Format::Vtp => "vtp",
+    }
+  }
+
+  fn write(self, writer: impl Write, points: &Points) -> io::Result<()> {
+    match self {
+      Format::Vtk => vtk::write(writer, points, false),
+      Format::VtkBinary => vtk::write(writer, points, true),
+      Format::Ply => ply::write(writer, points, false),
+      Format::PlyBinary => ply::write(writer, points, true),
+      Format::Vtp => vtp::write(writer, points),
+    }
+  }
+}
+
+impl FromStr for Format {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "vtk" => Ok(Format::Vtk),
+      "vtk-binary" => Ok(Format::VtkBinary),
+      "ply" => Ok(Format::Ply),
+      "ply-binary" => Ok(Format::PlyBinary),
+      "vtp" => Ok(Format::Vtp),
+      _ => Err(format!(
+        "unknown format `{s}`, expected vtk, vtk-binary, vtp, ply or \
+         ply-binary"
+      )),
+    }
+  }
+}
+
+impl fmt::Display for Format {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      Format::Vtk => "vtk",
+      Format::VtkBinary => "vtk-binary",
+      Format::Ply => "ply",
+      Format::PlyBinary => "ply-binary",
+      Format::Vtp => "vtp",
+    })
+  }
+}
+
+/// File format of the reconstructed fluid surfaces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SurfaceFormat {
+  #[default]
+  Obj,
+  Ply,
+  /// PLY, little-endian binary.
+  PlyBinary,
+}
+
+impl SurfaceFormat {
+  pub fn extension(self) -> &'static str {
+    match self {
+      SurfaceFormat::Obj => "obj",
+      SurfaceFormat::Ply | SurfaceFormat::PlyBinary => "ply",
+    }
+  }
+
+  fn write(
+    self,
+    writer: impl Write,
+    mesh: &Mesh,
+    title: &str,
+  ) -> io::Result<()> {
+    match self {
+      SurfaceFormat::Obj => obj::write(writer, mesh, title),
+      SurfaceFormat::Ply => ply::write_mesh(writer, mesh, title, false),
+      SurfaceFormat::PlyBinary => ply::write_mesh(writer, mesh, title, true),
+    }
+  }
+}
+
+impl FromStr for SurfaceFormat {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "obj" => Ok(SurfaceFormat::Obj),
+      "ply" => Ok(SurfaceFormat::Ply),
+      "ply-binary" => Ok(SurfaceFormat::PlyBinary),
+      _ => Err(format!(
+        "unknown surface format `{s}`, expected obj, ply or ply-binary"
+      )),
+    }
+  }
+}
+
+impl fmt::Display for SurfaceFormat {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      SurfaceFormat::Obj => "obj",
+      SurfaceFormat::Ply => "ply",
+      SurfaceFormat::PlyBinary => "ply-binary",
+    })
+  }
+}
+
+/// A point cloud with per-point attributes, the common input of the writers.
+pub struct Points {
+  pub title: String,
+  pub positions: Vec<[f32; 3]>,
+  pub attributes: Vec<Attribute>,
+}
+
+pub struct Attribute {
+  pub name: &'static str,
+  /// Amount of values per point.
+  pub components: usize,
+  pub data: Data,
+}
+
+pub enum Data {
+  Float(Vec<f32>),
+  Int(Vec<i32>),
+}
+
+impl Points {
+  /// Particles of every fluid, the `fluid` attribute is the index of the
+  /// fluid handle, stable across frames. Captured solver fields are added
+  /// when at least one fluid recorded them, as zeros for the fluids that did
+  /// not.
+  pub fn fluids(snapshot: &FluidsSnapshot, title: String) -> Self {
+    let mut positions = Vec::new();
+    let mut velocities = Vec::new();
+    let mut fluid_ids = Vec::new();
+    let mut densities0 = Vec::new();
+    let mut masses = Vec::new();
+
+    for (handle, fluid) in &snapshot.fluids {
+      let n = fluid.positions.len();
+      let id = handle.into_raw_parts().0;
+      positions.extend(fluid.positions.iter().map(|p| [p.x, p.y, p.z]));
+      velocities.extend(fluid.velocities.iter().flat_map(|v| [v.x, v.y, v.z]));
+      fluid_ids.extend(iter::repeat_n(id as i32, n));
+      densities0.extend(iter::repeat_n(fluid.density0, n));
+      masses.extend(iter::repeat_n(fluid.particle_mass, n));
+    }
+
+    let mut attributes = vec![
+      Attribute {
+        name: "velocity",
+        components: 3,
+        data: Data::Float(velocities),
+      },
+      Attribute { name: "fluid", components: 1, data: Data::Int(fluid_ids) },
+      Attribute {
+        name: "density0",
+        components: 1,
+        data: Data::Float(densities0),
+      },
+      Attribute { name: "mass", components: 1, data: Data::Float(masses) },
+    ];
+
+    let captured = |field: fn(&Fields) -> bool| {
+      snapshot.fluids.iter().any(|(_, fluid)| field(&fluid.fields))
+    };
+    if captured(|fields| !fields.accelerations.is_empty()) {
+      let accelerations = gather(snapshot, 3, |fields| {
+        fields.accelerations.iter().flat_map(|a| [a.x, a.y, a.z]).collect()
+      });
+      attributes.push(Attribute {
+        name: "acceleration",
+        components: 3,
+        data: Data::Float(accelerations),
+      });
+    }
+    if captured(|fields| !fields.densities.is_empty()) {
+      let densities = gather(snapshot, 1, |fields| fields.densities.clone());
+      let density_error =
+        gather(snapshot, 1, |fields| fields.density_error.clone());
+      let pressures = gather(snapshot, 1, |fields| fields.pressures.clone());
+      let neighbours = gather(snapshot, 1, |fields| {
+        fields.neighbours.iter().map(|&n| n as i32).collect()
+      });
+      attributes.extend([
+        Attribute {
+          name: "density",
+          components: 1,
+          data: Data::Float(densities),
+        },
+        Attribute {
+          name: "density_error",
+          components: 1,
+          data: Data::Float(density_error),
+        },
+        Attribute {
+          name: "pressure",
+          components: 1,
+          data: Data::Float(pressures),
+        },
+        Attribute {
+          name: "neighbours",
+          components: 1,
+          data: Data::Int(neighbours),
+        },
+      ]);
+    }
+
+    Self { title, positions, attributes }
+  }
+
+  /// The pose of every rigid body, with its rotation as a quaternion.
+  pub fn bodies(snapshot: &PhysicsSnapshot, title: String) -> Self {
+    let mut positions = Vec::new();
+    let mut rotations = Vec::new();
+    let mut linvels = Vec::new();
+
+    for (_, body) in snapshot.bodies.iter() {
+      let (pos, rot, vel) =
+        (body.translation(), body.rotation(), body.linvel());
+      positions.push([pos.x, pos.y, pos.z]);
+      rotations.extend([rot.i, rot.j, rot.k, rot.w]);
+      linvels.extend([vel.x, vel.y, vel.z]);
+    }
+
+    Self {
+      title,
+      positions,
+      attributes: vec![
+        Attribute {
+          name: "rotation",
+          components: 4,
+          data: Data::Float(rotations),
+        },
+        Attribute {
+          name: "velocity",
+          components: 3,
+          data: Data::Float(linvels),
+        },
+      ],
+    }
+  }
+}
+
+/// Concatenates a captured field of every fluid, `components` values per
+/// particle, with zeros for the fluids that did not capture it.
+fn gather<T: Copy + Default>(
+  snapshot: &FluidsSnapshot,
+  components: usize,
+  field: impl Fn(&Fields) -> Vec<T>,
+) -> Vec<T> {
+  let mut data = Vec::new();
+  for (_, fluid) in &snapshot.fluids {
+    let n = fluid.positions.len() * components;
+    let values = field(&fluid.fields);
+    if values.len() == n {
+      data.extend(values);
+    } else {
+      data.extend(iter::repeat_n(T::default(), n));
+    }
+  }
+  data
+}
+
+/// Writes every frame as a `fluids_<timestep>` and a `bodies_<timestep>` file
+/// into a directory, and optionally one `surface<fluid>_<timestep>` mesh per
+/// fluid.
+#[derive(Resource)]
+pub struct Exporter {
+  dir: PathBuf,
+  format: Format,
+  surfaces: Option<(Reconstruction, SurfaceFormat)>,
+}
+
+impl Exporter {
+  pub fn new(dir: impl Into<PathBuf>, format: Format) -> io::Result<Self> {
+    let dir = dir.into();
+    fs::create_dir_all(&dir)?;
+    Ok(Self { dir, format, surfaces: None })
+  }
+
+  /// Also reconstructs and writes the surface of every fluid.
+  pub fn with_surfaces(
+    mut self,
+    reconstruction: Reconstruction,
+    format: SurfaceFormat,
+  ) -> Self {
+    self.surfaces = Some((reconstruction, format));
+    self
+  }
+
+  pub fn dir(&self) -> &Path {
+    &self.dir
+  }
+
+  pub fn export(&self, (physics, fluids): &Frame) -> io::Result<()> {
+    let step = physics.timestep_id;
+    let title =
+      |kind| format!("flux {kind} timestep {step} t={}", physics.time);
+
+    self.write(
+      &format!("fluids_{step:06}"),
+      &Points::fluids(fluids, title("fluids")),
+    )?;
+    self.write(
+      &format!("bodies_{step:06}"),
+      &Points::bodies(physics, title("bodies")),
+    )?;
+
+    let Some((reconstruction, format)) = &self.surfaces else { return Ok(()) };
+    for (handle, fluid) in &fluids.fluids {
+      // Same id as the `fluid` attribute of the particles.
+      let id = handle.into_raw_parts().0;
+      let mesh =
+        reconstruction.reconstruct(&fluid.positions, fluids.particle_radius);
+      let path = self
+        .dir
+        .join(format!("surface{id}_{step:06}"))
+        .with_extension(format.extension());
+      let mut writer = BufWriter::new(File::create(path)?);
+      format.write(&mut writer, &mesh, &title("surface"))?;
+      writer.flush()?;
+    }
+    Ok(())
+  }
+
+  pub fn export_timeline(&self, timeline: &mut Timeline) -> io::Result<()> {
+    for index in 0..timeline.len() {
+      if let Some(frame) = timeline.get(index) {
+        self.export(frame)?;
+      }
+    }
+    Ok(())
+  }
+
+  fn write(&self, name: &str, points: &Points) -> io::Result<()> {
+    let path = self.path(name);
+    let mut writer = BufWriter::new(File::create(path)?);
+    self.format.write(&mut writer, points)?;
+    writer.flush()
+  }
+
+  fn path(&self, name: &str) -> PathBuf {
+    self.dir.join(name).with_extension(self.format.extension())
+  }
+}
@@ -0,0 +1,17 @@
+use {
+  crate::mesh::Mesh,
+  std::io::{self, Write},
+};
+
+/// Writes `mesh` as a Wavefront OBJ object.
+pub fn write(mut w: impl Write, mesh: &Mesh, title: &str) -> io::Result<()> {
+  writeln!(w, "# {}", title.replace('\n', " "))?;
+  for vertex in &mesh.vertices {
+    writeln!(w, "v {} {} {}", vertex.x, vertex.y, vertex.z)?;
+  }
+  // OBJ indices start at 1.
+  for [a, b, c] in &mesh.indices {
+    writeln!(w, "f {} {} {}", a + 1, b + 1, c + 1)?;
+  }
+  Ok(())
+}
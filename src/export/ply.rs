@@ -0,0 +1,144 @@
+use {
+  super::{Data, Points},
+  crate::mesh::Mesh,
+  std::{
+    fmt,
+    io::{self, Write},
+  },
+};
+
+const SUFFIXES: [&str; 4] = ["x", "y", "z", "w"];
+
+/// Writes `points` as a PLY vertex element, attributes with several
+/// components become one property per component.
+pub fn write(
+  mut w: impl Write,
+  points: &Points,
+  binary: bool,
+) -> io::Result<()> {
+  let n = points.positions.len();
+
+  writeln!(w, "ply")?;
+  if binary {
+    writeln!(w, "format binary_little_endian 1.0")?;
+  } else {
+    writeln!(w, "format ascii 1.0")?;
+  }
+  writeln!(w, "comment {}", points.title.replace('\n', " "))?;
+  writeln!(w, "element vertex {n}")?;
+  for axis in &SUFFIXES[..3] {
+    writeln!(w, "property float {axis}")?;
+  }
+  for attribute in &points.attributes {
+    let kind = match attribute.data {
+      Data::Float(_) => "float",
+      Data::Int(_) => "int",
+    };
+    for component in 0..attribute.components {
+      writeln!(
+        w,
+        "property {kind} {}",
+        property(attribute.name, attribute.components, component)
+      )?;
+    }
+  }
+  writeln!(w, "end_header")?;
+
+  for (i, position) in points.positions.iter().enumerate() {
+    let mut values: Vec<Value> =
+      position.iter().copied().map(Value::Float).collect();
+    for attribute in &points.attributes {
+      let range = i * attribute.components..(i + 1) * attribute.components;
+      match &attribute.data {
+        Data::Float(data) => {
+          values.extend(data[range].iter().copied().map(Value::Float))
+        }
+        Data::Int(data) => {
+          values.extend(data[range].iter().copied().map(Value::Int))
+        }
+      }
+    }
+
+    if binary {
+      for value in values {
+        match value {
+          Value::Float(x) => w.write_all(&x.to_le_bytes())?,
+          Value::Int(x) => w.write_all(&x.to_le_bytes())?,
+        }
+      }
+    } else {
+      let line: Vec<_> = values.iter().map(ToString::to_string).collect();
+      writeln!(w, "{}", line.join(" "))?;
+    }
+  }
+  Ok(())
+}
+
+/// Writes `mesh` as PLY vertex and face elements.
+pub fn write_mesh(
+  mut w: impl Write,
+  mesh: &Mesh,
+  title: &str,
+  binary: bool,
+) -> io::Result<()> {
+  writeln!(w, "ply")?;
+  if binary {
+    writeln!(w, "format binary_little_endian 1.0")?;
+  } else {
+    writeln!(w, "format ascii 1.0")?;
+  }
+  writeln!(w, "comment {}", title.replace('\n', " "))?;
+  writeln!(w, "element vertex {}", mesh.vertices.len())?;
+  for axis in &SUFFIXES[..3] {
+    writeln!(w, "property float {axis}")?;
+  }
+  writeln!(w, "element face {}", mesh.indices.len())?;
+  writeln!(w, "property list uchar int vertex_indices")?;
+  writeln!(w, "end_header")?;
+
+  for vertex in &mesh.vertices {
+    if binary {
+      for x in vertex.coords.iter() {
+        w.write_all(&x.to_le_bytes())?;
+      }
+    } else {
+      writeln!(w, "{} {} {}", vertex.x, vertex.y, vertex.z)?;
+    }
+  }
+  for &[a, b, c] in &mesh.indices {
+    if binary {
+      w.write_all(&[3])?;
+      for i in [a, b, c] {
+        w.write_all(&(i as i32).to_le_bytes())?;
+      }
+    } else {
+      writeln!(w, "3 {a} {b} {c}")?;
+    }
+  }
+  Ok(())
+}
+
+fn property(name: &str, components: usize, component: usize) -> String {
+  if components == 1 {
+    name.to_string()
+  } else if components <= SUFFIXES.len() {
+    format!("{name}_{}", SUFFIXES[component])
+  } else {
+    format!("{name}_{component}")
+  }
+}
+
+#[derive(Clone, Copy)]
+enum Value {
+  Float(f32),
+  Int(i32),
+}
+
+impl fmt::Display for Value {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Value::Float(x) => x.fmt(f),
+      Value::Int(x) => x.fmt(f),
+    }
+  }
+}
@@ -0,0 +1,91 @@
+use {
+  super::{Data, Points},
+  std::io::{self, Write},
+};
+
+/// Writes `points` as legacy VTK polydata with one vertex cell per point.
+pub fn write(
+  mut w: impl Write,
+  points: &Points,
+  binary: bool,
+) -> io::Result<()> {
+  let n = points.positions.len();
+
+  writeln!(w, "# vtk DataFile Version 3.0")?;
+  writeln!(w, "{}", points.title.replace('\n', " "))?;
+  writeln!(w, "{}", if binary { "BINARY" } else { "ASCII" })?;
+  writeln!(w, "DATASET POLYDATA")?;
+
+  writeln!(w, "POINTS {n} float")?;
+  floats(&mut w, points.positions.as_flattened(), 3, binary)?;
+
+  writeln!(w, "VERTICES {n} {}", 2 * n)?;
+  let cells: Vec<_> = (0..n as i32).flat_map(|i| [1, i]).collect();
+  ints(&mut w, &cells, 2, binary)?;
+
+  writeln!(w, "POINT_DATA {n}")?;
+  for attribute in &points.attributes {
+    let (name, components) = (attribute.name, attribute.components);
+    match &attribute.data {
+      Data::Float(data) if components == 3 => {
+        writeln!(w, "VECTORS {name} float")?;
+        floats(&mut w, data, components, binary)?;
+      }
+      Data::Float(data) => {
+        writeln!(w, "SCALARS {name} float {components}")?;
+        writeln!(w, "LOOKUP_TABLE default")?;
+        floats(&mut w, data, components, binary)?;
+      }
+      Data::Int(data) => {
+        writeln!(w, "SCALARS {name} int {components}")?;
+        writeln!(w, "LOOKUP_TABLE default")?;
+        ints(&mut w, data, components, binary)?;
+      }
+    }
+  }
+  Ok(())
+}
+
+fn floats(
+  w: &mut impl Write,
+  data: &[f32],
+  row: usize,
+  binary: bool,
+) -> io::Result<()> {
+  if binary {
+    for x in data {
+      w.write_all(&x.to_be_bytes())?;
+    }
+    writeln!(w)
+  } else {
+    lines(w, data, row)
+  }
+}
+
+fn ints(
+  w: &mut impl Write,
+  data: &[i32],
+  row: usize,
+  binary: bool,
+) -> io::Result<()> {
+  if binary {
+    for x in data {
+      w.write_all(&x.to_be_bytes())?;
+    }
+    writeln!(w)
+  } else {
+    lines(w, data, row)
+  }
+}
+
+fn lines(
+  w: &mut impl Write,
+  data: &[impl std::fmt::Display],
+  row: usize,
+) -> io::Result<()> {
+  for chunk in data.chunks(row) {
+    let line: Vec<_> = chunk.iter().map(ToString::to_string).collect();
+    writeln!(w, "{}", line.join(" "))?;
+  }
+  Ok(())
+}
@@ -0,0 +1,66 @@
+use {
+  super::{Data, Points},
+  std::{
+    fmt::Display,
+    io::{self, Write},
+  },
+};
+
+/// Writes `points` as ASCII VTK XML polydata with one vertex cell per point.
+pub fn write(mut w: impl Write, points: &Points) -> io::Result<()> {
+  let n = points.positions.len();
+
+  writeln!(w, r#"<?xml version="1.0"?>"#)?;
+  writeln!(w, "<!-- {} -->", points.title.replace("--", "-"))?;
+  writeln!(
+    w,
+    r#"<VTKFile type="PolyData" version="0.1" byte_order="LittleEndian">"#
+  )?;
+  writeln!(w, "<PolyData>")?;
+  writeln!(w, r#"<Piece NumberOfPoints="{n}" NumberOfVerts="{n}""#)?;
+  writeln!(w, r#"  NumberOfLines="0" NumberOfStrips="0" NumberOfPolys="0">"#)?;
+
+  writeln!(w, "<PointData>")?;
+  for attribute in &points.attributes {
+    let (name, components) = (attribute.name, attribute.components);
+    match &attribute.data {
+      Data::Float(data) => {
+        array(&mut w, "Float32", Some(name), components, data)?
+      }
+      Data::Int(data) => array(&mut w, "Int32", Some(name), components, data)?,
+    }
+  }
+  writeln!(w, "</PointData>")?;
+
+  writeln!(w, "<Points>")?;
+  array(&mut w, "Float32", None, 3, points.positions.as_flattened())?;
+  writeln!(w, "</Points>")?;
+
+  writeln!(w, "<Verts>")?;
+  let connectivity: Vec<_> = (0..n as i32).collect();
+  array(&mut w, "Int32", Some("connectivity"), 1, &connectivity)?;
+  let offsets: Vec<_> = (1..=n as i32).collect();
+  array(&mut w, "Int32", Some("offsets"), 1, &offsets)?;
+  writeln!(w, "</Verts>")?;
+
+  writeln!(w, "</Piece>")?;
+  writeln!(w, "</PolyData>")?;
+  writeln!(w, "</VTKFile>")
+}
+
+fn array(
+  w: &mut impl Write,
+  kind: &str,
+  name: Option<&str>,
+  components: usize,
+  data: &[impl Display],
+) -> io::Result<()> {
+  let name = name.map(|name| format!(r#" Name="{name}""#)).unwrap_or_default();
+  writeln!(w, r#"<DataArray type="{kind}"{name}"#)?;
+  writeln!(w, r#"  NumberOfComponents="{components}" format="ascii">"#)?;
+  for chunk in data.chunks(components.max(1)) {
+    let line: Vec<_> = chunk.iter().map(ToString::to_string).collect();
+    writeln!(w, "{}", line.join(" "))?;
+  }
+  writeln!(w, "</DataArray>")
+}
@@ -2,14 +2,26 @@ use {
   crate::{
     harness::{self, PhysicsEvents, PhysicsState, RunState},
     prelude::*,
+    probe::{Particles, Probe, Sample, Series},
+    sph::{CubicSpline, Grid},
+  },
+  rapier::{
+    dynamics::{RigidBodyHandle, RigidBodySet},
+    geometry::{ColliderHandle, ColliderSet},
+    math::{Point, Vector},
   },
-  rapier::math::{Point, Vector},
   salva::{
     LiquidWorld,
-    integrations::rapier::FluidsPipeline,
-    object::{BoundaryHandle, FluidHandle},
+    integrations::rapier::{ColliderSampling, FluidsPipeline},
+    object::{
+      BoundaryHandle, FluidHandle, interaction_groups::InteractionGroups,
+    },
+  },
+  serde::{Deserialize, Serialize},
+  std::{
+    collections::HashMap, fmt, mem, ops::AddAssign, str::FromStr,
+    time::Duration,
   },
-  std::time::Duration,
 };
 
 /// A user-defined callback executed at each frame.
@@ -29,25 +41,121 @@ impl Default for Fluids {
   }
 }
 
+/// How [`Fluids::couple`] turns a collider into boundary particles.
+pub enum Sampling {
+  /// Samples the volume of the collider shape once.
+  Volume,
+  /// Uses these particles, given in the local frame of the collider.
+  Points(Vec<Point<Real>>),
+  /// Samples the collider around its contacts with the fluids each step,
+  /// for large or moving shapes.
+  Dynamic,
+}
+
+/// How much of the fluids a [`FluidsSnapshot`] records, each level adding to
+/// the previous one at the cost of capture time and memory.
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  Default,
+  PartialEq,
+  Eq,
+  PartialOrd,
+  Ord,
+  Serialize,
+  Deserialize,
+)]
+pub enum CaptureLevel {
+  /// Positions and velocities.
+  #[default]
+  Kinematics,
+  /// The accelerations of the last step.
+  Accelerations,
+  /// Densities, density errors, pressures and neighbour counts. The solver
+  /// keeps its own buffers private, so they are estimated with a neighbour
+  /// search of the captured particles rather than read from it.
+  Solver,
+}
+
+impl FromStr for CaptureLevel {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "kinematics" => Ok(CaptureLevel::Kinematics),
+      "accelerations" => Ok(CaptureLevel::Accelerations),
+      "solver" => Ok(CaptureLevel::Solver),
+      _ => Err(format!(
+        "unknown capture level `{s}`, expected kinematics, accelerations or \
+         solver"
+      )),
+    }
+  }
+}
+
+impl fmt::Display for CaptureLevel {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      CaptureLevel::Kinematics => "kinematics",
+      CaptureLevel::Accelerations => "accelerations",
+      CaptureLevel::Solver => "solver",
+    })
+  }
+}
+
+/// The force and torque the fluids exert on a body through the boundaries
+/// of its colliders, the torque being about its center of mass. Gravity and
+/// contacts are left out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct FluidForce {
+  pub force: Vector<Real>,
+  pub torque: Vector<Real>,
+}
+
+impl AddAssign for FluidForce {
+  fn add_assign(&mut self, other: Self) {
+    self.force += other.force;
+    self.torque += other.torque;
+  }
+}
+
 /// A plugin for rendering fluids with the Rapier harness.
 pub struct Fluids {
   pub pipeline: FluidsPipeline,
   callbacks: Vec<FluidCallback>,
+  probes: Vec<(Probe, Series)>,
+  /// The boundary of every coupled collider.
+  couplings: HashMap<ColliderHandle, BoundaryHandle>,
+  forces: Vec<(RigidBodyHandle, FluidForce)>,
   step_time: f64,
+  capture: CaptureLevel,
 }
 
 impl Fluids {
   /// Initializes the plugin.
   pub fn new() -> Self {
+    Self::from_pipeline(FluidsPipeline::new(0.025, 2.0))
+  }
+
+  pub fn from_pipeline(pipeline: FluidsPipeline) -> Self {
     Self {
-      pipeline: FluidsPipeline::new(0.025, 2.0),
+      pipeline,
       callbacks: Vec::new(),
+      probes: Vec::new(),
+      couplings: HashMap::new(),
+      forces: Vec::new(),
       step_time: 0.0,
+      capture: CaptureLevel::default(),
     }
   }
 
-  pub fn from_pipeline(pipeline: FluidsPipeline) -> Self {
-    Self { pipeline, callbacks: Vec::new(), step_time: 0.0 }
+  pub fn capture_level(&self) -> CaptureLevel {
+    self.capture
+  }
+
+  pub fn set_capture_level(&mut self, level: CaptureLevel) {
+    self.capture = level;
   }
 
   /// Adds a callback to be executed at each frame.
@@ -65,10 +173,144 @@ impl Fluids {
     self.callbacks.push(Box::new(f))
   }
 
+  /// Samples `probe` after every step into the returned series.
+  pub fn add_probe(&mut self, probe: Probe) -> Series {
+    let series = Series::default();
+    self.probes.push((probe, series.clone()));
+    series
+  }
+
+  /// The forces of the last step on every body with a coupled collider,
+  /// ordered by handle. Fixed and kinematic bodies are reported as well,
+  /// although the coupling only moves dynamic ones.
+  ///
+  /// When the fluid solver splits a step into substeps, only the forces of
+  /// the last substep are known, so the reported forces are those rather
+  /// than their average over the step.
+  pub fn forces(&self) -> &[(RigidBodyHandle, FluidForce)] {
+    &self.forces
+  }
+
+  pub fn force(&self, body: RigidBodyHandle) -> Option<FluidForce> {
+    self
+      .forces
+      .iter()
+      .find(|(handle, _)| *handle == body)
+      .map(|&(_, force)| force)
+  }
+
   /// Sets the fluids pipeline used by the harness.
   pub fn set_pipeline(&mut self, pipeline: FluidsPipeline) {
     self.pipeline = pipeline;
     self.pipeline.liquid_world.counters.enable();
+    self.couplings.clear();
+    self.forces.clear();
+  }
+
+  /// Makes `collider` a boundary of the fluids sharing `groups`, the boundary
+  /// following the collider wherever it moves. Returns `None` when the
+  /// collider does not exist or its shape cannot be sampled.
+  pub fn couple(
+    &mut self,
+    colliders: &ColliderSet,
+    collider: ColliderHandle,
+    sampling: Sampling,
+    groups: InteractionGroups,
+  ) -> Option<BoundaryHandle> {
+    let shape = colliders.get(collider)?.shape();
+    let radius = self.pipeline.liquid_world.particle_radius();
+    let sampling = match sampling {
+      Sampling::Volume => ColliderSampling::StaticSampling(
+        salva::sampling::shape_volume_ray_sample(shape, radius)?,
+      ),
+      Sampling::Points(points) => ColliderSampling::StaticSampling(points),
+      Sampling::Dynamic => ColliderSampling::DynamicContactSampling,
+    };
+
+    let boundary = self
+      .pipeline
+      .liquid_world
+      .add_boundary(salva::object::Boundary::new(Vec::new(), groups));
+    self.pipeline.coupling.register_coupling(boundary, collider, sampling);
+    self.couplings.insert(collider, boundary);
+    Some(boundary)
+  }
+
+  /// Removes the boundary of `collider`, to be called before removing the
+  /// collider itself.
+  pub fn decouple(
+    &mut self,
+    collider: ColliderHandle,
+  ) -> Option<BoundaryHandle> {
+    let boundary = self.pipeline.coupling.unregister_coupling(collider)?;
+    self.pipeline.liquid_world.remove_boundary(boundary);
+    self.couplings.remove(&collider);
+    Some(boundary)
+  }
+
+  /// Removes the boundaries of every collider attached to `body`.
+  pub fn decouple_body(
+    &mut self,
+    bodies: &RigidBodySet,
+    body: RigidBodyHandle,
+  ) {
+    let Some(body) = bodies.get(body) else { return };
+    for &collider in body.colliders() {
+      self.decouple(collider);
+    }
+  }
+
+  /// Rewinds the particles to `snapshot`.
+  ///
+  /// Fluids and boundaries keep their handles, density and non-pressure
+  /// forces. Fluids missing from `snapshot` are emptied, while snapshot
+  /// fluids that were removed from the world since are ignored.
+  pub fn restore(&mut self, snapshot: &FluidsSnapshot) {
+    use salva::object;
+
+    let world = &mut self.pipeline.liquid_world;
+    let radius = world.particle_radius();
+
+    for (handle, fluid) in world.fluids_mut().iter_mut() {
+      let (positions, velocities) = snapshot
+        .fluids
+        .iter()
+        .find(|(captured, _)| *captured == handle)
+        .map(|(_, captured)| {
+          (captured.positions.clone(), captured.velocities.clone())
+        })
+        .unwrap_or_default();
+
+      let mut restored = object::Fluid::new(
+        positions,
+        radius,
+        fluid.density0,
+        fluid.interaction_groups,
+      );
+      restored.velocities = velocities;
+      // `Fluid::new` gives every particle a volume of (2r)³, while the fluid
+      // may have been built with others, like a close packing.
+      let count = restored.volumes.len();
+      let mut volumes = mem::take(&mut fluid.volumes);
+      if let Some(&last) = volumes.last() {
+        volumes.resize(count, last);
+        restored.volumes = volumes;
+      }
+      restored.nonpressure_forces = mem::take(&mut fluid.nonpressure_forces);
+      *fluid = restored;
+    }
+
+    for (handle, boundary) in world.boundaries_mut().iter_mut() {
+      if let Some((_, captured)) =
+        snapshot.boundaries.iter().find(|(captured, _)| *captured == handle)
+      {
+        *boundary = object::Boundary::new(
+          captured.positions.clone(),
+          boundary.interaction_groups,
+        );
+      }
+    }
+    self.forces.clone_from(&snapshot.forces);
   }
 
   fn liquid_world(&self) -> &LiquidWorld {
@@ -76,33 +318,87 @@ impl Fluids {
   }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Fluid {
   pub positions: Vec<Point<Real>>,
   pub velocities: Vec<Vector<Real>>,
+  /// Rest density.
+  pub density0: Real,
+  /// Mass of a single particle.
+  pub particle_mass: Real,
+  pub fields: Fields,
 }
 
+/// Speed of sound of the weakly compressible equation of state the
+/// estimated [`Fields::pressures`] follow.
+pub const SOUND_SPEED: Real = 20.0;
+
+/// Per-particle data recorded above [`CaptureLevel::Kinematics`], every
+/// field being empty below the level capturing it.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Fields {
+  /// From [`CaptureLevel::Accelerations`], read from the solver.
+  pub accelerations: Vec<Vector<Real>>,
+  /// From [`CaptureLevel::Solver`], like the fields below, all of them
+  /// estimates: the SPH sum over the fluid particles and the boundary
+  /// particles, which weigh as much as the same volume of the fluid.
+  pub densities: Vec<Real>,
+  /// The compression `max(density / density0 - 1, 0)` the pressure solver
+  /// corrects.
+  pub density_error: Vec<Real>,
+  /// `density0 * SOUND_SPEED² * density_error`, the pressure a weakly
+  /// compressible fluid would push the compression back with.
+  pub pressures: Vec<Real>,
+  /// Fluid and boundary particles within the kernel radius, the particle
+  /// itself excluded.
+  pub neighbours: Vec<u32>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Boundary {
   pub positions: Vec<Point<Real>>,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct FluidsSnapshot {
   pub fluids: Vec<(FluidHandle, Fluid)>,
   pub boundaries: Vec<(BoundaryHandle, Boundary)>,
+  /// See [`Fluids::forces`].
+  pub forces: Vec<(RigidBodyHandle, FluidForce)>,
   pub particle_radius: f32,
+  /// Kernel radius relative to the particle diameter.
+  pub smoothing_factor: f32,
 }
 
-impl harness::Plugin for Fluids {
+impl harness::Capture for Fluids {
   type Snapshot = FluidsSnapshot;
 
   fn snapshot(&self) -> Self::Snapshot {
     use salva::object;
 
+    let world = self.liquid_world();
+    let mut estimated = match self.capture {
+      CaptureLevel::Solver => estimated_fields(world),
+      _ => Vec::new(),
+    }
+    .into_iter();
+
     let fluid = |(handle, fluid): (FluidHandle, &object::Fluid)| {
+      let mut fields = estimated.next().unwrap_or_default();
+      if self.capture >= CaptureLevel::Accelerations {
+        fields.accelerations = fluid.accelerations.to_vec();
+      }
       (
         handle,
         Fluid {
           positions: fluid.positions.to_vec(),
           velocities: fluid.velocities.to_vec(),
+          density0: fluid.density0,
+          particle_mass: fluid
+            .volumes
+            .first()
+            .map_or(0.0, |volume| volume * fluid.density0),
+          fields,
         },
       )
     };
@@ -110,14 +406,128 @@ impl harness::Plugin for Fluids {
       (handle, Boundary { positions: boundary.positions.to_vec() })
     };
 
-    let world = self.liquid_world();
     FluidsSnapshot {
       fluids: world.fluids().iter().map(fluid).collect(),
       boundaries: world.boundaries().iter().map(boundary).collect(),
+      forces: self.forces.clone(),
       particle_radius: world.particle_radius(),
+      smoothing_factor: world.h() / (2.0 * world.particle_radius()),
     }
   }
+}
+
+/// Sums the forces of the last substep on the boundary particles of every
+/// coupled collider per body, the same forces the coupling applies.
+fn fluid_forces(
+  world: &LiquidWorld,
+  couplings: &HashMap<ColliderHandle, BoundaryHandle>,
+  colliders: &ColliderSet,
+  bodies: &RigidBodySet,
+) -> Vec<(RigidBodyHandle, FluidForce)> {
+  let mut forces = HashMap::<_, FluidForce>::new();
+  for (&collider, &boundary) in couplings {
+    let Some(handle) = colliders.get(collider).and_then(|co| co.parent())
+    else {
+      continue;
+    };
+    let (Some(body), Some(boundary)) =
+      (bodies.get(handle), world.boundaries().get(boundary))
+    else {
+      continue;
+    };
+
+    let center = body.center_of_mass();
+    let particles = boundary.forces.read().unwrap();
+    let total = forces.entry(handle).or_default();
+    for (point, force) in boundary.positions.iter().zip(particles.iter()) {
+      *total +=
+        FluidForce { force: *force, torque: (point - center).cross(force) };
+    }
+  }
+
+  let mut forces: Vec<_> = forces.into_iter().collect();
+  forces.sort_by_key(|(handle, _)| handle.into_raw_parts());
+  forces
+}
+
+/// What a neighbour contributes to the density of a fluid particle.
+#[derive(Clone, Copy)]
+enum Neighbour {
+  /// A fluid particle of this mass.
+  Fluid(Real),
+  /// A boundary particle of this volume, weighing as much as the same volume
+  /// of the fluid it pushes.
+  Boundary(Real),
+}
+
+/// The estimated [`Fields`] of every fluid, summed over the fluid and
+/// boundary particles within the kernel radius of the world.
+fn estimated_fields(world: &LiquidWorld) -> Vec<Fields> {
+  let fluids: Vec<_> = world.fluids().iter().map(|(_, fluid)| fluid).collect();
+
+  // Volumes are only known once the world stepped, missing ones count as
+  // empty.
+  let volume =
+    |volumes: &[Real], i: usize| volumes.get(i).copied().unwrap_or_default();
+  let mut points = Vec::new();
+  let mut kinds = Vec::new();
+  for fluid in &fluids {
+    points.extend(fluid.positions.iter().copied());
+    kinds.extend(
+      (0..fluid.positions.len())
+        .map(|i| Neighbour::Fluid(volume(&fluid.volumes, i) * fluid.density0)),
+    );
+  }
+  for (_, boundary) in world.boundaries().iter() {
+    points.extend(boundary.positions.iter().copied());
+    kinds.extend(
+      (0..boundary.positions.len())
+        .map(|i| Neighbour::Boundary(volume(&boundary.volumes, i))),
+    );
+  }
+
+  let kernel = CubicSpline::new(world.h());
+  let grid = Grid::new(&points, kernel.h);
+
+  let mut offset = 0;
+  fluids
+    .iter()
+    .map(|fluid| {
+      let n = fluid.positions.len();
+      let mut densities = Vec::with_capacity(n);
+      let mut neighbours = Vec::with_capacity(n);
+      for i in offset..offset + n {
+        let (mut density, mut count) = (0.0, 0);
+        for j in grid.neighbours(&points[i]) {
+          let w = kernel.value(na::distance(&points[i], &points[j]));
+          density += match kinds[j] {
+            Neighbour::Fluid(mass) => mass * w,
+            Neighbour::Boundary(volume) => volume * fluid.density0 * w,
+          };
+          count += u32::from(i != j);
+        }
+        densities.push(density);
+        neighbours.push(count);
+      }
+      offset += n;
+
+      let density_error: Vec<_> = densities
+        .iter()
+        .map(|density| (density / fluid.density0 - 1.0).max(0.0))
+        .collect();
+      let stiffness = fluid.density0 * SOUND_SPEED.powi(2);
+      Fields {
+        pressures: density_error.iter().map(|e| stiffness * e).collect(),
+        densities,
+        density_error,
+        neighbours,
+        ..Fields::default()
+      }
+    })
+    .collect()
+}
 
+impl harness::Plugin for Fluids {
   fn run_callbacks(
     &mut self,
     physics: &mut PhysicsState,
@@ -128,6 +538,18 @@ impl harness::Plugin for Fluids {
     for callback in &mut self.callbacks {
       callback(delta, physics, physics_events, &mut self.pipeline, run_state)
     }
+
+    if self.probes.is_empty() {
+      return;
+    }
+    let dt = delta.as_secs_f32();
+    let world = &self.pipeline.liquid_world;
+    let particles = Particles::new(world);
+    let neighbours = particles.neighbours(world.h());
+    for (probe, series) in &self.probes {
+      let reading = probe.measure(self, &neighbours, dt);
+      series.lock().push(Sample { time: run_state.time + dt, reading });
+    }
   }
 
   fn step(&mut self, physics: &mut PhysicsState, _run_state: &RunState) {
@@ -138,6 +560,12 @@ impl harness::Plugin for Fluids {
       &physics.colliders,
       &mut physics.bodies,
     );
+    self.forces = fluid_forces(
+      &self.pipeline.liquid_world,
+      &self.couplings,
+      &physics.colliders,
+      &physics.bodies,
+    );
     self.step_time = instant::now() - step_time;
   }
 
@@ -145,56 +573,3 @@ impl harness::Plugin for Fluids {
     format!("Fluids: {:.2}ms", self.step_time)
   }
 }
-
-impl snapshot::Snapshot for FluidsSnapshot {
-  fn draw(&self, graphics: &mut Gizmos) {
-    let FluidsSnapshot { fluids, boundaries, particle_radius } = self;
-
-    // нужно красить по скорости от максимальной до минимальной
-    for (_, fluid) in fluids.iter() {
-      let velocities: Vec<_> =
-        fluid.velocities.iter().map(Vector::magnitude).collect();
-      let max =
-        velocities.iter().copied().max_by(Real::total_cmp).unwrap_or(f32::MAX);
-
-      use bevy::math::VectorSpace;
-
-      for (i, particle) in fluid.positions.iter().enumerate() {
-        let vel = velocities[i];
-        graphics
-          .sphere(
-            Isometry3d::from_translation(Vec3::from_slice(
-              particle.coords.as_slice(),
-            )),
-            *particle_radius,
-            Srgba::rgb(0.0, 0.2, 0.65)
-              .lerp(Srgba::rgb(1.0, 0.5, 0.85), vel / max),
-          )
-          .resolution(4);
-      }
-    }
-
-    for (_, boundary) in boundaries {
-      for particle in boundary
-        .positions
-        .iter()
-        .enumerate()
-        .filter_map(flate::<_, { 3 * 3 * 3 }>)
-      {
-        graphics
-          .sphere(
-            Isometry3d::from_translation(Vec3::from_slice(
-              particle.coords.as_slice(),
-            )),
-            *particle_radius,
-            Color::srgb(1.0, 0.2, 0.65),
-          )
-          .resolution(4);
-      }
-    }
-  }
-}
-
-fn flate<T, const N: usize>((i, t): (usize, T)) -> Option<T> {
-  (i % N == 0).then_some(t)
-}
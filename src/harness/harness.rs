@@ -1,3 +1,5 @@
+use std::any::Any;
+
 use rapier::{
   dynamics::{
     CCDSolver, ImpulseJointSet, IntegrationParameters, IslandManager,
@@ -10,7 +12,10 @@ use rapier::{
   },
 };
 
-use super::{PhysicsEvents, PhysicsState};
+use {
+  super::{PhysicsEvents, PhysicsState},
+  crate::snapshot::PhysicsSnapshot,
+};
 
 pub struct RunState {
   #[cfg(feature = "parallel")]
@@ -72,15 +77,14 @@ pub struct Harness {
   pub physics: PhysicsState,
   max_steps: usize,
   callbacks: Callbacks,
+  plugins: Vec<Box<dyn Plugin>>,
   pub events: PhysicsEvents,
   event_handler: ChannelEventCollector,
 }
 
-pub trait Plugin {
-  type Snapshot;
-
-  fn snapshot(&self) -> Self::Snapshot;
-
+/// Extends the [`Harness`] step, see [`Harness::step`] for the order in which
+/// plugins are invoked. Plugins step on the thread pool of the harness.
+pub trait Plugin: Any + Send {
   fn run_callbacks(
     &mut self,
     physics: &mut PhysicsState,
@@ -93,6 +97,13 @@ pub trait Plugin {
   fn profiling_string(&self) -> String;
 }
 
+/// A plugin whose state can be captured for playback.
+pub trait Capture {
+  type Snapshot;
+
+  fn snapshot(&self) -> Self::Snapshot;
+}
+
 type Callbacks =
   Vec<Box<dyn FnMut(&mut PhysicsState, &PhysicsEvents, &RunState) + Send>>;
 
@@ -115,6 +126,7 @@ impl Harness {
       },
       event_handler: ChannelEventCollector::new(collisions.0, contacts.0),
       callbacks: vec![],
+      plugins: vec![],
     }
   }
 
@@ -193,6 +205,25 @@ impl Harness {
     self.physics.pipeline.counters.enable();
   }
 
+  /// Rewinds the physics to `snapshot`, keeping plugins and callbacks.
+  pub fn restore(&mut self, snapshot: &PhysicsSnapshot) {
+    let physics = &mut self.physics;
+    physics.islands = snapshot.island_manager.clone();
+    physics.broad_phase = snapshot.broad_phase.clone();
+    physics.narrow_phase = snapshot.narrow_phase.clone();
+    physics.bodies = snapshot.bodies.clone();
+    physics.colliders = snapshot.colliders.clone();
+    physics.impulse_joints = snapshot.impulse_joints.clone();
+    physics.multibody_joints = snapshot.multibody_joints.clone();
+    physics.integration_parameters.dt = snapshot.dt;
+    physics.ccd_solver = CCDSolver::new();
+    physics.query_pipeline = QueryPipeline::new();
+
+    self.state.timestep_id = snapshot.timestep_id;
+    self.state.time = snapshot.time;
+    self.events.poll_all();
+  }
+
   pub fn add_callback<
     F: FnMut(&mut PhysicsState, &PhysicsEvents, &RunState) + Send + 'static,
   >(
@@ -202,6 +233,32 @@ impl Harness {
     self.callbacks.push(Box::new(callback));
   }
 
+  pub fn add_plugin(&mut self, plugin: impl Plugin) {
+    self.plugins.push(Box::new(plugin));
+  }
+
+  pub fn plugin<P: Plugin>(&self) -> Option<&P> {
+    self
+      .plugins
+      .iter()
+      .find_map(|plugin| (plugin.as_ref() as &dyn Any).downcast_ref())
+  }
+
+  pub fn plugin_mut<P: Plugin>(&mut self) -> Option<&mut P> {
+    self
+      .plugins
+      .iter_mut()
+      .find_map(|plugin| (plugin.as_mut() as &mut dyn Any).downcast_mut())
+  }
+
+  pub fn profiling_string(&self) -> String {
+    let plugins: Vec<_> =
+      self.plugins.iter().map(|plugin| plugin.profiling_string()).collect();
+    plugins.join(" ")
+  }
+
+  /// Steps the physics pipeline, then every plugin, then the harness
+  /// callbacks and finally the callbacks of every plugin.
   // #[profiling::function]
   pub fn step(&mut self) {
     let Self { event_handler, physics, .. } = self;
@@ -229,10 +286,28 @@ impl Harness {
     #[cfg(not(feature = "parallel"))]
     step();
 
+    let Self { plugins, physics, state, .. } = self;
+    let state = &*state;
+    let step_plugins = || {
+      for plugin in plugins {
+        plugin.step(physics, state);
+      }
+    };
+
+    #[cfg(feature = "parallel")]
+    state.thread_pool.install(step_plugins);
+
+    #[cfg(not(feature = "parallel"))]
+    step_plugins();
+
     for callback in &mut self.callbacks {
       callback(&mut self.physics, &self.events, &self.state);
     }
 
+    for plugin in &mut self.plugins {
+      plugin.run_callbacks(&mut self.physics, &self.events, &self.state);
+    }
+
     self.events.poll_all();
 
     self.state.time += self.physics.integration_parameters.dt;
@@ -3,7 +3,10 @@ mod harness;
 mod physics;
 
 pub use {
-  fluids::{Boundary, Fluid, Fluids, FluidsSnapshot},
-  harness::{Harness, Plugin, RunState},
+  fluids::{
+    Boundary, CaptureLevel, Fields, Fluid, FluidForce, Fluids, FluidsSnapshot,
+    Sampling,
+  },
+  harness::{Capture, Harness, Plugin, RunState},
   physics::{PhysicsEvents, PhysicsState},
 };
@@ -1,8 +1,24 @@
-use salva::{
-  math::{Point, Vector},
-  object::Fluid,
+use {
+  parry::{
+    query::{PointQuery, Ray, RayCast},
+    shape::Shape,
+  },
+  rand::{Rng, SeedableRng, rngs::StdRng},
+  salva::{
+    math::{Point, Vector},
+    object::{Fluid, interaction_groups::InteractionGroups},
+    parry, sampling,
+  },
 };
 
+fn from_points(
+  points: Vec<Point<f32>>,
+  particle_rad: f32,
+  density: f32,
+) -> Fluid {
+  Fluid::new(points, particle_rad, density, InteractionGroups::default())
+}
+
 pub fn cube_fluid(
   ni: usize,
   nj: usize,
@@ -27,7 +43,117 @@ pub fn cube_fluid(
     }
   }
 
-  use salva::object::interaction_groups::InteractionGroups;
+  from_points(points, particle_rad, density)
+}
 
-  Fluid::new(points, particle_rad, density, InteractionGroups::default())
+/// Samples the volume of any shape centered at its local origin.
+pub fn shape_fluid(
+  shape: &dyn Shape,
+  particle_rad: f32,
+  density: f32,
+) -> Option<Fluid> {
+  let points = sampling::shape_volume_ray_sample(shape, particle_rad)?;
+  Some(from_points(points, particle_rad, density))
+}
+
+/// Hexagonal close packing of the box of `half_extents`, denser than the
+/// cubic lattice of [`cube_fluid`] for the same particle radius. Each
+/// particle gets the `4√2 r³` of space it packs into, so that the block
+/// starts at rest density.
+pub fn hcp_fluid(
+  half_extents: Vector<f32>,
+  particle_rad: f32,
+  density: f32,
+) -> Fluid {
+  let r = particle_rad;
+  let (dy, dz) = (3.0f32.sqrt() * r, 2.0 * (2.0f32 / 3.0).sqrt() * r);
+  let count = |extent: f32, step: f32| (2.0 * extent / step).ceil() as i32;
+
+  let mut points = Vec::new();
+  for k in 0..count(half_extents.z, dz) {
+    for j in 0..count(half_extents.y, dy) {
+      for i in 0..count(half_extents.x, 2.0 * r) {
+        let x = (2 * i + (j + k) % 2) as f32 * r;
+        let y = (j as f32 + (k % 2) as f32 / 3.0) * dy;
+        let z = k as f32 * dz;
+        let point = Point::new(x, y, z) + Vector::repeat(r) - half_extents;
+        if (half_extents - point.coords.abs()).min() >= r {
+          points.push(point);
+        }
+      }
+    }
+  }
+
+  let mut fluid = from_points(points, particle_rad, density);
+  fluid.volumes.fill(4.0 * 2.0f32.sqrt() * r.powi(3));
+  fluid
+}
+
+/// Fills the inside of `container` up to the local height `level`.
+///
+/// The container is usually hollow, like a tank made of walls or a mesh, so
+/// a point is kept when it lies outside of the container walls while rays
+/// cast from it down and sideways all hit them.
+pub fn fill_fluid(
+  container: &dyn Shape,
+  level: f32,
+  particle_rad: f32,
+  density: f32,
+) -> Fluid {
+  let aabb = container.compute_local_aabb();
+  let spacing = 2.0 * particle_rad;
+  let reach = aabb.extents().norm();
+  let directions =
+    [-Vector::y(), Vector::x(), -Vector::x(), Vector::z(), -Vector::z()];
+
+  let enclosed = |point: &Point<f32>| {
+    container.distance_to_local_point(point, true) >= particle_rad
+      && directions.iter().all(|&dir| {
+        container.cast_local_ray(&Ray::new(*point, dir), reach, true).is_some()
+      })
+  };
+
+  let top = level.min(aabb.maxs.y);
+  let count = |min: f32, max: f32| ((max - min) / spacing).floor() as usize;
+  let (ni, nj, nk) = (
+    count(aabb.mins.x, aabb.maxs.x),
+    count(aabb.mins.y, top),
+    count(aabb.mins.z, aabb.maxs.z),
+  );
+
+  let mut points = Vec::new();
+  for i in 0..ni {
+    for j in 0..nj {
+      for k in 0..nk {
+        let offset = Vector::new(i as f32, j as f32, k as f32) * spacing;
+        let point = aabb.mins + Vector::repeat(particle_rad) + offset;
+        if enclosed(&point) {
+          points.push(point);
+        }
+      }
+    }
+  }
+
+  from_points(points, particle_rad, density)
+}
+
+/// Offsets every particle by up to `amplitude` along each axis, breaking the
+/// symmetry of lattices.
+pub fn jittered(mut fluid: Fluid, amplitude: f32, seed: u64) -> Fluid {
+  let mut rng = StdRng::seed_from_u64(seed);
+  for point in &mut fluid.positions {
+    *point += Vector::from_fn(|_, _| rng.gen_range(-amplitude..=amplitude));
+  }
+  fluid
+}
+
+/// Sets the velocity of every particle from its position.
+pub fn with_velocity_field(
+  mut fluid: Fluid,
+  field: impl Fn(&Point<f32>) -> Vector<f32>,
+) -> Fluid {
+  for (velocity, point) in fluid.velocities.iter_mut().zip(&fluid.positions) {
+    *velocity = field(point);
+  }
+  fluid
 }
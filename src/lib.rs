@@ -1,11 +1,18 @@
 #![feature(let_chains)]
 extern crate nalgebra as na;
 
+pub mod color;
 mod core;
+pub mod export;
 pub mod harness;
 pub mod helper;
+pub mod mesh;
+pub mod probe;
+pub mod scene;
 pub mod snapshot;
+pub mod sph;
 pub mod stand;
+pub mod surface;
 
 pub use prelude::*;
 
@@ -13,7 +20,7 @@ pub mod prelude {
   pub use {
     crate::{
       core::*,
-      harness, helper, snapshot,
+      harness, helper, scene, snapshot,
       stand::{self, Frame},
     },
     bevy::prelude::*,
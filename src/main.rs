@@ -1,34 +1,96 @@
 #![feature(let_chains)]
 
-use flux::prelude::*;
-
-use rapier::{
-  dynamics::{
-    ImpulseJointSet, MultibodyJointSet, RigidBodyBuilder, RigidBodySet,
-  },
-  geometry::{ColliderBuilder, ColliderSet},
-  prelude::*,
-};
+mod cli;
 
 use {
-  flux::harness::Fluids,
-  harness::Harness,
-  nalgebra::Isometry3,
-  salva::{
-    integrations::rapier::{ColliderSampling, FluidsPipeline},
-    object::{Boundary, interaction_groups::InteractionGroups},
-    sampling,
-  },
+  bevy::log::tracing_subscriber::{self, EnvFilter},
+  clap::error::ErrorKind,
+  flux::{export::Exporter, prelude::*, probe::Probes},
+  stand::{Headless, Timeline},
+  std::path::{Path, PathBuf},
 };
 
 fn main() {
-  let mut app = flux::app();
-  app.add_systems(Startup, setup);
+  let args = cli::Args::parse();
+
+  if let Some(path) = &args.replay {
+    let timeline = Timeline::load(path).unwrap_or_else(|err| {
+      cli::exit(ErrorKind::Io, format!("{}: {err}", path.display()))
+    });
+
+    if let Some(exporter) = args.exporter() {
+      export(&exporter, timeline);
+      return;
+    }
+
+    let mut app = flux::app();
+    app
+      .add_systems(Startup, setup)
+      .insert_resource(stand::Surfaces::new(args.reconstruction()));
+    stand::replay(&mut app, timeline);
+    app.run();
+  } else if args.headless {
+    headless(&args);
+  } else {
+    let mut app = flux::app();
+    app.add_systems(Startup, setup);
+
+    stand::plugin(&mut app, args.stand());
+    let overrides = args.clone();
+    stand::watch(&mut app, &args.scene, move |scene| overrides.apply(scene));
+    if let Some(exporter) = args.exporter() {
+      app.insert_resource(exporter);
+    }
+    app
+      .insert_resource(Timeline::with_config(args.timeline()))
+      .insert_resource(Recording {
+        timeline: args.record.clone(),
+        probes: args.probes.clone(),
+      })
+      .add_systems(Last, save_on_exit);
+    app.run();
+  }
+}
+
+fn headless(args: &cli::Args) {
+  init_logging();
+
+  let mut runner = Headless::new(args.stand())
+    .with_progress(args.progress)
+    .with_decimation(args.timeline().decimation);
+  if args.record.is_some() {
+    runner = runner.with_timeline(args.timeline());
+  }
+  if let Some(exporter) = args.exporter() {
+    runner = runner.with_exporter(exporter);
+  }
+  runner.run(args.max_steps.unwrap_or_default());
+
+  if let Some(dir) = &args.probes {
+    save_probes(runner.probes(), dir);
+  }
+  if let Some(path) = &args.record
+    && let Some(timeline) = runner.into_timeline()
+  {
+    save(&timeline, path);
+  }
+}
+
+fn export(exporter: &Exporter, mut timeline: Timeline) {
+  init_logging();
 
-  let (harness, fluids) = stand();
+  let dir = exporter.dir();
+  match exporter.export_timeline(&mut timeline) {
+    Ok(()) => info!("exported {} frames to {}", timeline.len(), dir.display()),
+    Err(err) => cli::exit(ErrorKind::Io, format!("{}: {err}", dir.display())),
+  }
+}
 
-  stand::plugin(&mut app, harness, fluids);
-  app.run();
+/// Logs like `LogPlugin` for the runs without `DefaultPlugins`.
+fn init_logging() {
+  let filter = EnvFilter::try_from_default_env()
+    .unwrap_or_else(|_| EnvFilter::new("info,wgpu=error,naga=warn"));
+  tracing_subscriber::fmt().with_env_filter(filter).init();
 }
 
 fn setup(mut commands: Commands) {
@@ -40,45 +102,40 @@ fn setup(mut commands: Commands) {
   ));
 }
 
-const PARTICLE_RADIUS: f32 = 0.05;
-const SMOOTHING_FACTOR: f32 = 2.0;
-
-fn stand() -> (Harness, Fluids) {
-  let mut bodies = RigidBodySet::new();
-  let mut colliders = ColliderSet::new();
-  let impulse_joints = ImpulseJointSet::new();
-  let multibody_joints = MultibodyJointSet::new();
-  let mut fluids_pipeline =
-    FluidsPipeline::new(PARTICLE_RADIUS, SMOOTHING_FACTOR);
-
-  let ground_thickness = 0.2;
-  let ground_half_width = 2.5;
-
-  let ground_shape =
-    SharedShape::cuboid(ground_half_width, ground_thickness, ground_half_width);
-
-  let ground_body = RigidBodyBuilder::fixed().build();
-  let ground_handle = bodies.insert(ground_body);
-
-  let samples =
-    sampling::shape_volume_ray_sample(&*ground_shape, PARTICLE_RADIUS).unwrap();
-  let co = ColliderBuilder::new(ground_shape)
-    .position(Isometry3::translation(0.0, -10.0, 0.0))
-    .build();
-  let co_handle = colliders.insert_with_parent(co, ground_handle, &mut bodies);
-  let bo_handle = fluids_pipeline
-    .liquid_world
-    .add_boundary(Boundary::new(Vec::new(), InteractionGroups::default()));
-
-  fluids_pipeline.coupling.register_coupling(
-    bo_handle,
-    co_handle,
-    ColliderSampling::StaticSampling(samples),
-  );
-
-  let mut harness =
-    Harness::new(bodies, colliders, impulse_joints, multibody_joints);
-  harness.integration_parameters_mut().dt = 1.0 / 200.0;
-
-  (harness, Fluids::from_pipeline(fluids_pipeline))
+/// Where the run is saved when the window closes.
+#[derive(Resource)]
+struct Recording {
+  timeline: Option<PathBuf>,
+  probes: Option<PathBuf>,
+}
+
+fn save_on_exit(
+  mut exit: EventReader<AppExit>,
+  recording: Res<Recording>,
+  timeline: Res<Timeline>,
+  probes: Res<Probes>,
+) {
+  if exit.read().next().is_none() {
+    return;
+  }
+  if let Some(path) = &recording.timeline {
+    save(&timeline, path);
+  }
+  if let Some(dir) = &recording.probes {
+    save_probes(&probes, dir);
+  }
+}
+
+fn save(timeline: &Timeline, path: &Path) {
+  match timeline.save(path) {
+    Ok(()) => info!("saved {} frames to {}", timeline.len(), path.display()),
+    Err(err) => error!("failed to save {}: {err}", path.display()),
+  }
+}
+
+fn save_probes(probes: &Probes, dir: &Path) {
+  match probes.write_csv(dir) {
+    Ok(()) => info!("saved {} probes to {}", probes.0.len(), dir.display()),
+    Err(err) => error!("failed to save probes to {}: {err}", dir.display()),
+  }
 }
@@ -0,0 +1,153 @@
+use {
+  crate::prelude::*,
+  bevy::{
+    asset::RenderAssetUsages,
+    render::mesh::{self as render, Indices, PrimitiveTopology},
+  },
+  rapier::{
+    geometry::SharedShape,
+    math::{Point, Vector},
+  },
+  std::{
+    collections::HashSet,
+    fs::File,
+    io::{self, BufReader},
+    path::Path,
+  },
+};
+
+/// A triangle mesh loaded from an OBJ or STL file.
+#[derive(Debug, Clone, Default)]
+pub struct Mesh {
+  pub vertices: Vec<Point<Real>>,
+  pub indices: Vec<[u32; 3]>,
+}
+
+fn invalid(
+  error: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+impl Mesh {
+  /// Loads every object of the file, picking the format from its extension.
+  pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+    let path = path.as_ref();
+    let extension = path
+      .extension()
+      .and_then(|ext| ext.to_str())
+      .map(str::to_ascii_lowercase);
+
+    match extension.as_deref() {
+      Some("obj") => Self::obj(path),
+      Some("stl") => Self::stl(path),
+      _ => Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{}: expected an .obj or .stl mesh", path.display()),
+      )),
+    }
+  }
+
+  fn obj(path: &Path) -> io::Result<Self> {
+    let (models, _) =
+      tobj::load_obj(path, &tobj::GPU_LOAD_OPTIONS).map_err(invalid)?;
+
+    let mut mesh = Self::default();
+    for model in models {
+      let offset = mesh.vertices.len() as u32;
+      mesh.vertices.extend(
+        model
+          .mesh
+          .positions
+          .chunks_exact(3)
+          .map(|p| Point::new(p[0], p[1], p[2])),
+      );
+      mesh.indices.extend(
+        model
+          .mesh
+          .indices
+          .chunks_exact(3)
+          .map(|i| [i[0] + offset, i[1] + offset, i[2] + offset]),
+      );
+    }
+    Ok(mesh)
+  }
+
+  fn stl(path: &Path) -> io::Result<Self> {
+    let stl = stl_io::read_stl(&mut BufReader::new(File::open(path)?))?;
+    Ok(Self {
+      vertices: stl
+        .vertices
+        .iter()
+        .map(|&stl_io::Vector([x, y, z])| Point::new(x, y, z))
+        .collect(),
+      indices: stl
+        .faces
+        .iter()
+        .map(|face| face.vertices.map(|i| i as u32))
+        .collect(),
+    })
+  }
+
+  pub fn scaled(mut self, scale: Vector<Real>) -> Self {
+    for vertex in &mut self.vertices {
+      vertex.coords.component_mul_assign(&scale);
+    }
+    self
+  }
+
+  /// A smooth shaded mesh for rendering.
+  pub fn to_render(&self) -> render::Mesh {
+    let positions: Vec<_> =
+      self.vertices.iter().map(|p| [p.x, p.y, p.z]).collect();
+    render::Mesh::new(
+      PrimitiveTopology::TriangleList,
+      RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(render::Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_indices(Indices::U32(self.indices.as_flattened().to_vec()))
+    .with_computed_smooth_normals()
+  }
+
+  /// An exact, hollow collider, suited for containers and static obstacles.
+  pub fn trimesh(&self) -> io::Result<SharedShape> {
+    SharedShape::trimesh(self.vertices.clone(), self.indices.clone())
+      .map_err(invalid)
+  }
+
+  /// A compound of convex parts, suited for dynamic bodies.
+  pub fn convex_decomposition(&self) -> SharedShape {
+    SharedShape::convex_decomposition(&self.vertices, &self.indices)
+  }
+
+  /// Samples the surface of every triangle with a spacing of `2 * radius`,
+  /// the boundary particles of a hollow mesh.
+  pub fn surface_samples(&self, radius: Real) -> Vec<Point<Real>> {
+    let spacing = 2.0 * radius;
+    let mut seen = HashSet::new();
+    let mut samples = Vec::new();
+
+    for &[a, b, c] in &self.indices {
+      let [a, b, c] = [a, b, c].map(|i| self.vertices[i as usize]);
+      let (ab, ac) = (b - a, c - a);
+      let nu = (ab.norm() / spacing).ceil().max(1.0) as usize;
+      let nv = (ac.norm() / spacing).ceil().max(1.0) as usize;
+
+      for i in 0..=nu {
+        for j in 0..=nv {
+          let (u, v) = (i as Real / nu as Real, j as Real / nv as Real);
+          if u + v > 1.0 + Real::EPSILON {
+            continue;
+          }
+          let point = a + ab * u + ac * v;
+          // Triangles sharing an edge sample it twice.
+          let key = (point.coords / radius).map(|x| x.round() as i32);
+          if seen.insert(key) {
+            samples.push(point);
+          }
+        }
+      }
+    }
+    samples
+  }
+}
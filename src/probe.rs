@@ -0,0 +1,372 @@
+use {
+  crate::{
+    harness::{FluidForce, Fluids},
+    prelude::*,
+    sph::{CubicSpline, Grid},
+  },
+  rapier::{
+    dynamics::RigidBodyHandle,
+    math::{Isometry, Point, Vector},
+  },
+  salva::LiquidWorld,
+  std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+  },
+};
+
+/// A sensor measuring the fluids after every step, added with
+/// [`Fluids::add_probe`].
+#[derive(Debug, Clone)]
+pub enum Probe {
+  /// Velocity and density interpolated at a point with the SPH kernel.
+  Point { position: Point<Real> },
+  /// Mass and volume flowing per second through a rectangle of the local
+  /// `xy` plane, positive along the local `z` axis.
+  Flux { pose: Isometry<Real>, half_extents: [Real; 2] },
+  /// Particles inside a box.
+  Volume { pose: Isometry<Real>, half_extents: Vector<Real> },
+  /// Height of the free surface along the line from `start` to `end`,
+  /// measured from `start`.
+  Level { start: Point<Real>, end: Point<Real> },
+  /// Force and torque of the fluids on a body, see [`Fluids::forces`].
+  Body { body: RigidBodyHandle },
+}
+
+/// What a [`Probe`] measured at one step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reading {
+  Point {
+    velocity: Vector<Real>,
+    density: Real,
+  },
+  Flux {
+    mass: Real,
+    volume: Real,
+  },
+  Volume {
+    count: usize,
+    mean_velocity: Vector<Real>,
+    /// Particle volume over the box volume.
+    fill: Real,
+  },
+  /// `None` while the line is dry.
+  Level {
+    level: Option<Real>,
+  },
+  Force {
+    force: Vector<Real>,
+    torque: Vector<Real>,
+  },
+}
+
+impl Reading {
+  /// Names of the [`values`](Self::values).
+  pub fn columns(&self) -> &'static [&'static str] {
+    match self {
+      Reading::Point { .. } => &["vx", "vy", "vz", "density"],
+      Reading::Flux { .. } => &["mass_flow", "volume_flow"],
+      Reading::Volume { .. } => &["count", "vx", "vy", "vz", "fill"],
+      Reading::Level { .. } => &["level"],
+      Reading::Force { .. } => &["fx", "fy", "fz", "tx", "ty", "tz"],
+    }
+  }
+
+  pub fn values(&self) -> Vec<Real> {
+    match *self {
+      Reading::Point { velocity: v, density } => vec![v.x, v.y, v.z, density],
+      Reading::Flux { mass, volume } => vec![mass, volume],
+      Reading::Volume { count, mean_velocity: v, fill } => {
+        vec![count as Real, v.x, v.y, v.z, fill]
+      }
+      Reading::Level { level } => vec![level.unwrap_or(Real::NAN)],
+      Reading::Force { force: f, torque: t } => {
+        vec![f.x, f.y, f.z, t.x, t.y, t.z]
+      }
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+  /// Simulation time at the end of the measured step.
+  pub time: Real,
+  pub reading: Reading,
+}
+
+/// The samples of a probe, shared with the [`Fluids`] plugin recording them.
+#[derive(Debug, Clone, Default)]
+pub struct Series(Arc<Mutex<Vec<Sample>>>);
+
+impl Series {
+  /// Locks the samples, recording waits until the guard is dropped.
+  pub fn lock(&self) -> MutexGuard<'_, Vec<Sample>> {
+    self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+  }
+
+  pub fn last(&self) -> Option<Sample> {
+    self.lock().last().copied()
+  }
+
+  /// Drops the samples taken after `time`, when the simulation rewinds.
+  pub fn truncate(&self, time: Real) {
+    self.lock().retain(|sample| sample.time <= time);
+  }
+
+  /// Writes the samples as CSV, one row per step.
+  pub fn write_csv(&self, mut w: impl Write) -> io::Result<()> {
+    let samples = self.lock();
+    let Some(first) = samples.first() else { return Ok(()) };
+
+    writeln!(w, "time,{}", first.reading.columns().join(","))?;
+    for sample in samples.iter() {
+      let values: Vec<_> =
+        sample.reading.values().iter().map(ToString::to_string).collect();
+      writeln!(w, "{},{}", sample.time, values.join(","))?;
+    }
+    Ok(())
+  }
+}
+
+/// Named probes of a stand.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct Probes(pub Vec<(String, Series)>);
+
+impl Probes {
+  pub fn get(&self, name: &str) -> Option<&Series> {
+    self.0.iter().find(|(probe, _)| probe == name).map(|(_, series)| series)
+  }
+
+  pub fn truncate(&self, time: Real) {
+    for (_, series) in &self.0 {
+      series.truncate(time);
+    }
+  }
+
+  /// Writes every probe as `<name>.csv` into `dir`.
+  pub fn write_csv(&self, dir: impl AsRef<Path>) -> io::Result<()> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir)?;
+    for (name, series) in &self.0 {
+      let path = dir.join(name).with_extension("csv");
+      let mut writer = BufWriter::new(File::create(path)?);
+      series.write_csv(&mut writer)?;
+      writer.flush()?;
+    }
+    Ok(())
+  }
+}
+
+impl Probe {
+  /// Measures `fluids` just after a step of `dt`, `neighbours` holding
+  /// their particles.
+  pub fn measure(
+    &self,
+    fluids: &Fluids,
+    neighbours: &Neighbours,
+    dt: Real,
+  ) -> Reading {
+    let world = &fluids.pipeline.liquid_world;
+    match self {
+      Probe::Point { position } => {
+        let kernel = CubicSpline::new(world.h());
+        let (mut density, mut weight) = (0.0, 0.0);
+        let mut velocity = Vector::zeros();
+        for particle in neighbours.fluid(position) {
+          let w = kernel.value(na::distance(position, &particle.position));
+          density += particle.mass * w;
+          weight += particle.volume * w;
+          velocity += particle.velocity * (particle.volume * w);
+        }
+        // Boundary particles weigh as much as the same volume of the nearby
+        // fluid, as in the solver, so that walls do not lower the density.
+        let mut boundary = 0.0;
+        for (point, volume) in neighbours.boundary(position) {
+          boundary += volume * kernel.value(na::distance(position, point));
+        }
+        // Normalized, so that a partially filled kernel still gives the
+        // velocity of the nearby particles.
+        if weight > 0.0 {
+          let density0 = density / weight;
+          density += boundary * density0;
+          velocity /= weight;
+        }
+        Reading::Point { velocity, density }
+      }
+      Probe::Flux { pose, half_extents: [hx, hy] } => {
+        let (mut mass, mut volume) = (0.0, 0.0);
+        for particle in &neighbours.particles.fluid {
+          // Particles moved by exactly `velocity * dt` during the step.
+          let to = pose.inverse_transform_point(&particle.position);
+          let from = pose.inverse_transform_point(
+            &(particle.position - particle.velocity * dt),
+          );
+          if (from.z < 0.0) == (to.z < 0.0) {
+            continue;
+          }
+          let crossing = from + (to - from) * (from.z / (from.z - to.z));
+          if crossing.x.abs() > *hx || crossing.y.abs() > *hy {
+            continue;
+          }
+          let sign = if to.z >= 0.0 { 1.0 } else { -1.0 };
+          mass += sign * particle.mass;
+          volume += sign * particle.volume;
+        }
+        Reading::Flux { mass: mass / dt, volume: volume / dt }
+      }
+      Probe::Volume { pose, half_extents } => {
+        let (mut count, mut volume) = (0, 0.0);
+        let mut velocity = Vector::zeros();
+        for particle in &neighbours.particles.fluid {
+          let local = pose.inverse_transform_point(&particle.position);
+          if (half_extents - local.coords.abs()).min() < 0.0 {
+            continue;
+          }
+          count += 1;
+          volume += particle.volume;
+          velocity += particle.velocity;
+        }
+        Reading::Volume {
+          count,
+          mean_velocity: if count == 0 {
+            velocity
+          } else {
+            velocity / count as Real
+          },
+          fill: volume / (8.0 * half_extents.product()),
+        }
+      }
+      Probe::Level { start, end } => {
+        Reading::Level { level: level(world, neighbours, start, end) }
+      }
+      &Probe::Body { body } => {
+        let FluidForce { force, torque } =
+          fluids.force(body).unwrap_or_default();
+        Reading::Force { force, torque }
+      }
+    }
+  }
+}
+
+struct Particle {
+  position: Point<Real>,
+  velocity: Vector<Real>,
+  volume: Real,
+  mass: Real,
+}
+
+/// The fluid and boundary particles after a step, gathered once for every
+/// probe.
+pub struct Particles {
+  fluid: Vec<Particle>,
+  positions: Vec<Point<Real>>,
+  boundary: Vec<Point<Real>>,
+  boundary_volumes: Vec<Real>,
+}
+
+impl Particles {
+  pub fn new(world: &LiquidWorld) -> Self {
+    let fallback = (2.0 * world.particle_radius()).powi(3);
+    let fluid: Vec<_> = world
+      .fluids()
+      .iter()
+      .flat_map(|(_, fluid)| {
+        (0..fluid.positions.len()).map(move |i| {
+          let volume = fluid.volumes.get(i).copied().unwrap_or(fallback);
+          Particle {
+            position: fluid.positions[i],
+            velocity: fluid.velocities[i],
+            volume,
+            mass: volume * fluid.density0,
+          }
+        })
+      })
+      .collect();
+    let positions = fluid.iter().map(|particle| particle.position).collect();
+
+    let (mut boundary, mut boundary_volumes) = (Vec::new(), Vec::new());
+    for (_, particles) in world.boundaries().iter() {
+      let n = particles.positions.len();
+      boundary.extend(particles.positions.iter().copied());
+      boundary_volumes.extend(
+        (0..n).map(|i| particles.volumes.get(i).copied().unwrap_or_default()),
+      );
+    }
+    Self { fluid, positions, boundary, boundary_volumes }
+  }
+
+  /// Buckets the particles for queries within `h`, the kernel radius.
+  pub fn neighbours(&self, h: Real) -> Neighbours<'_> {
+    Neighbours {
+      particles: self,
+      fluid: Grid::new(&self.positions, h),
+      boundary: Grid::new(&self.boundary, h),
+    }
+  }
+}
+
+/// [`Particles`] bucketed by the kernel radius, so that probes only visit
+/// the particles around the points they sample.
+pub struct Neighbours<'a> {
+  particles: &'a Particles,
+  fluid: Grid<'a>,
+  boundary: Grid<'a>,
+}
+
+impl Neighbours<'_> {
+  fn fluid(&self, point: &Point<Real>) -> impl Iterator<Item = &Particle> {
+    self.fluid.neighbours(point).map(|i| &self.particles.fluid[i])
+  }
+
+  fn boundary(
+    &self,
+    point: &Point<Real>,
+  ) -> impl Iterator<Item = (&Point<Real>, Real)> {
+    self.boundary.neighbours(point).map(|i| {
+      (&self.particles.boundary[i], self.particles.boundary_volumes[i])
+    })
+  }
+}
+
+/// Distance from `start` to the first point of the line where the colour
+/// field falls below one half after having been above it, or to the last
+/// wet point when the line is submerged up to `end`.
+fn level(
+  world: &LiquidWorld,
+  neighbours: &Neighbours,
+  start: &Point<Real>,
+  end: &Point<Real>,
+) -> Option<Real> {
+  const ISO: Real = 0.5;
+
+  let kernel = CubicSpline::new(world.h());
+  let length = na::distance(start, end);
+  let axis = (end - start).try_normalize(Real::EPSILON)?;
+
+  let color = |point: &Point<Real>| -> Real {
+    neighbours
+      .fluid(point)
+      .map(|p| p.volume * kernel.value(na::distance(point, &p.position)))
+      .sum()
+  };
+
+  let spacing = world.particle_radius();
+  let steps = (length / spacing).ceil().max(1.0) as usize;
+  let mut wet = None;
+  let mut previous = (0.0, color(start));
+  for i in 1..=steps {
+    let along = (i as Real * spacing).min(length);
+    let value = color(&(start + axis * along));
+    if value >= ISO {
+      wet = Some(along);
+    } else if previous.1 >= ISO {
+      // Interpolates the crossing of the iso level.
+      let t = (previous.1 - ISO) / (previous.1 - value);
+      return Some(previous.0 + (along - previous.0) * t);
+    }
+    previous = (along, value);
+  }
+  wet
+}
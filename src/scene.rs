@@ -0,0 +1,707 @@
+use {
+  crate::{
+    harness::{CaptureLevel, Fluids, Harness},
+    helper,
+    mesh::Mesh,
+    prelude::*,
+    probe::{self, Probes},
+    stand::{
+      Inflow, Outflow, Profile, Rate, Schedule, ShapeFlow, ShapeSink, Stand,
+    },
+    surface::Reconstruction,
+  },
+  na::Isometry3,
+  rapier::{
+    dynamics::{
+      ImpulseJointSet, MultibodyJointSet, RigidBodyBuilder, RigidBodyHandle,
+      RigidBodySet, RigidBodyType,
+    },
+    geometry::{ColliderBuilder, ColliderSet, SharedShape},
+    math::{Point, Vector},
+  },
+  salva::{
+    integrations::rapier::FluidsPipeline,
+    object::interaction_groups::InteractionGroups,
+    parry::bounding_volume::Aabb, sampling, solver,
+  },
+  serde::{Deserialize, Serialize},
+  std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+  },
+};
+
+/// A declarative description of a stand, usually stored as RON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Scene {
+  pub particle_radius: Real,
+  pub smoothing_factor: Real,
+  pub gravity: [Real; 3],
+  pub integration: Integration,
+  pub bodies: Vec<Body>,
+  pub fluids: Vec<Fluid>,
+  pub emitters: Vec<Emitter>,
+  pub sinks: Vec<Sink>,
+  pub probes: Vec<Probe>,
+  /// How much of the fluids each recorded frame holds.
+  pub capture: CaptureLevel,
+  /// How the fluid surfaces are shown and exported.
+  pub surface: Reconstruction,
+}
+
+impl Default for Scene {
+  fn default() -> Self {
+    Self {
+      particle_radius: 0.025,
+      smoothing_factor: 2.0,
+      gravity: [0.0, -9.81, 0.0],
+      integration: Integration::default(),
+      bodies: Vec::new(),
+      fluids: Vec::new(),
+      emitters: Vec::new(),
+      sinks: Vec::new(),
+      probes: Vec::new(),
+      capture: CaptureLevel::default(),
+      surface: Reconstruction::default(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Integration {
+  pub dt: Real,
+  pub solver_iterations: usize,
+}
+
+impl Default for Integration {
+  fn default() -> Self {
+    Self { dt: 1.0 / 60.0, solver_iterations: 4 }
+  }
+}
+
+/// A translation and a rotation given as a scaled axis.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Pose {
+  pub translation: [Real; 3],
+  pub rotation: [Real; 3],
+}
+
+impl Pose {
+  pub fn isometry(&self) -> Isometry3<Real> {
+    Isometry3::new(self.translation.into(), self.rotation.into())
+  }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum BodyKind {
+  #[default]
+  Fixed,
+  Dynamic,
+  Kinematic,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Body {
+  pub kind: BodyKind,
+  pub position: Pose,
+  pub linvel: [Real; 3],
+  pub colliders: Vec<Collider>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Collider {
+  pub shape: Shape,
+  pub position: Pose,
+  pub density: Real,
+  pub friction: Real,
+  pub restitution: Real,
+  /// Samples the collider as a fluid boundary.
+  pub boundary: Option<Sampling>,
+}
+
+impl Default for Collider {
+  fn default() -> Self {
+    Self {
+      shape: Shape::Ball { radius: 0.5 },
+      position: Pose::default(),
+      density: 1.0,
+      friction: 0.5,
+      restitution: 0.0,
+      boundary: None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Shape {
+  Cuboid {
+    half_extents: [Real; 3],
+  },
+  Ball {
+    radius: Real,
+  },
+  Capsule {
+    half_height: Real,
+    radius: Real,
+  },
+  Cylinder {
+    half_height: Real,
+    radius: Real,
+  },
+  Cone {
+    half_height: Real,
+    radius: Real,
+  },
+  /// An OBJ or STL file, relative to the scene file. Kept as a hollow
+  /// triangle mesh unless `decompose` splits it into convex parts.
+  Mesh {
+    path: PathBuf,
+    #[serde(default = "unit_scale")]
+    scale: [Real; 3],
+    #[serde(default)]
+    decompose: bool,
+  },
+}
+
+fn unit_scale() -> [Real; 3] {
+  [1.0; 3]
+}
+
+impl Shape {
+  /// The collision shape, meshes are taken from `meshes`.
+  fn shared(&self, meshes: &mut Meshes) -> io::Result<SharedShape> {
+    Ok(match *self {
+      Shape::Cuboid { half_extents: [x, y, z] } => SharedShape::cuboid(x, y, z),
+      Shape::Ball { radius } => SharedShape::ball(radius),
+      Shape::Capsule { half_height, radius } => {
+        SharedShape::capsule_y(half_height, radius)
+      }
+      Shape::Cylinder { half_height, radius } => {
+        SharedShape::cylinder(half_height, radius)
+      }
+      Shape::Cone { half_height, radius } => {
+        SharedShape::cone(half_height, radius)
+      }
+      Shape::Mesh { ref path, scale, decompose } => {
+        meshes.shared(path, scale, decompose)?
+      }
+    })
+  }
+
+  /// Particles sampling the shape as a static fluid boundary, the surface of
+  /// meshes and the volume of primitives.
+  fn boundary(
+    &self,
+    radius: Real,
+    meshes: &mut Meshes,
+  ) -> io::Result<Vec<Point<Real>>> {
+    if let Shape::Mesh { path, scale, .. } = self {
+      return Ok(meshes.scaled(path, *scale)?.surface_samples(radius));
+    }
+    sampling::shape_volume_ray_sample(&*self.shared(meshes)?, radius)
+      .ok_or_else(|| invalid(format!("cannot sample {self:?}")))
+  }
+
+  fn resolve(&mut self, dir: &Path) {
+    if let Shape::Mesh { path, .. } = self
+      && path.is_relative()
+    {
+      *path = dir.join(&*path);
+    }
+  }
+}
+
+/// The mesh files of a scene being built, each read once and each of its
+/// scaled shapes built once, however many colliders, fluids, emitters and
+/// sinks use them.
+#[derive(Default)]
+struct Meshes {
+  files: HashMap<PathBuf, Mesh>,
+  shapes: HashMap<(PathBuf, [u32; 3], bool), SharedShape>,
+}
+
+impl Meshes {
+  fn file(&mut self, path: &Path) -> io::Result<&Mesh> {
+    if !self.files.contains_key(path) {
+      let mesh = Mesh::load(path).map_err(|err| {
+        io::Error::new(err.kind(), format!("{}: {err}", path.display()))
+      })?;
+      self.files.insert(path.to_owned(), mesh);
+    }
+    Ok(&self.files[path])
+  }
+
+  fn scaled(&mut self, path: &Path, scale: [Real; 3]) -> io::Result<Mesh> {
+    Ok(self.file(path)?.clone().scaled(scale.into()))
+  }
+
+  fn shared(
+    &mut self,
+    path: &Path,
+    scale: [Real; 3],
+    decompose: bool,
+  ) -> io::Result<SharedShape> {
+    let key = (path.to_owned(), scale.map(Real::to_bits), decompose);
+    if let Some(shape) = self.shapes.get(&key) {
+      return Ok(shape.clone());
+    }
+    let mesh = self.scaled(path, scale)?;
+    let shape =
+      if decompose { mesh.convex_decomposition() } else { mesh.trimesh()? };
+    self.shapes.insert(key, shape.clone());
+    Ok(shape)
+  }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Sampling {
+  /// Samples the shape volume once, for bodies that never deform.
+  Static,
+  /// Samples the shape around the fluid contacts each step.
+  Dynamic,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Fluid {
+  pub name: String,
+  pub volume: Volume,
+  pub position: Pose,
+  pub density: Real,
+  pub velocity: [Real; 3],
+  /// Spin of the fluid as a whole around `position`, added to `velocity`.
+  pub angular_velocity: [Real; 3],
+  /// Random offset of the particles, relative to the particle radius.
+  pub jitter: Real,
+  pub forces: Vec<Force>,
+}
+
+impl Default for Fluid {
+  fn default() -> Self {
+    Self {
+      name: String::new(),
+      volume: Volume::Cube { size: [0; 3] },
+      position: Pose::default(),
+      density: 1000.0,
+      velocity: [0.0; 3],
+      angular_velocity: [0.0; 3],
+      jitter: 0.0,
+      forces: Vec::new(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Volume {
+  /// A lattice of `size` particles centered at the origin.
+  Cube {
+    size: [usize; 3],
+  },
+  Shape(Shape),
+  /// A hexagonal close packing of the box centered at the origin.
+  Packed {
+    half_extents: [Real; 3],
+  },
+  /// The inside of a hollow container, usually a mesh, up to `level`.
+  Fill {
+    container: Shape,
+    level: Real,
+  },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Force {
+  XSPHViscosity { fluid: Real, boundary: Real },
+  ArtificialViscosity { fluid: Real, boundary: Real },
+  DFSPHViscosity { fluid: Real, boundary: Real },
+  Akinci2013SurfaceTension { tension: Real, adhesion: Real },
+}
+
+impl Force {
+  fn boxed(self) -> Box<dyn solver::NonPressureForce> {
+    match self {
+      Force::XSPHViscosity { fluid, boundary } => {
+        Box::new(solver::XSPHViscosity::new(fluid, boundary))
+      }
+      Force::ArtificialViscosity { fluid, boundary } => {
+        Box::new(solver::ArtificialViscosity::new(fluid, boundary))
+      }
+      Force::DFSPHViscosity { fluid, boundary } => {
+        Box::new(solver::DFSPHViscosity::new(fluid, boundary))
+      }
+      Force::Akinci2013SurfaceTension { tension, adhesion } => {
+        Box::new(solver::Akinci2013SurfaceTension::new(tension, adhesion))
+      }
+    }
+  }
+}
+
+/// An inflow adding particles to the fluid called `fluid` each step.
+///
+/// When `body` is set, the emitter follows the body with this index in
+/// [`Scene::bodies`], `center` and `velocity` being local to the body.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Emitter {
+  pub fluid: String,
+  pub shape: Shape,
+  pub center: [Real; 3],
+  /// Orientation of the shape and its velocity, as a scaled axis.
+  #[serde(default)]
+  pub rotation: [Real; 3],
+  #[serde(default)]
+  pub velocity: [Real; 3],
+  /// Emits the section of the shape in its `xz` plane layer by layer.
+  #[serde(default)]
+  pub layered: bool,
+  #[serde(default)]
+  pub profile: Profile,
+  /// Random offset of the particles, relative to the particle radius.
+  #[serde(default)]
+  pub jitter: Real,
+  #[serde(default)]
+  pub body: Option<usize>,
+  #[serde(default)]
+  pub rate: Rate,
+  #[serde(default)]
+  pub schedule: Schedule,
+  /// Maximum amount of emitted particles.
+  #[serde(default)]
+  pub budget: Option<usize>,
+}
+
+/// An outflow deleting the particles of `fluids`, or of every fluid when
+/// empty, that enter its region.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sink {
+  #[serde(default)]
+  pub fluids: Vec<String>,
+  pub region: Region,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Region {
+  Shape {
+    shape: Shape,
+    #[serde(default)]
+    position: Pose,
+  },
+  /// Everything outside of the box between `mins` and `maxs`.
+  Outside { mins: [Real; 3], maxs: [Real; 3] },
+}
+
+/// A sensor sampling the fluids every step, its time series being written
+/// to `<name>.csv`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Probe {
+  pub name: String,
+  pub gauge: Gauge,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Gauge {
+  /// Velocity and density at `position`.
+  Point { position: [Real; 3] },
+  /// Mass and volume flow through the rectangle of the local `xy` plane,
+  /// positive along the local `z` axis.
+  Flux {
+    #[serde(default)]
+    position: Pose,
+    half_extents: [Real; 2],
+  },
+  /// Particle count, mean velocity and fill ratio of a box.
+  Volume {
+    #[serde(default)]
+    position: Pose,
+    half_extents: [Real; 3],
+  },
+  /// Height of the free surface along the line from `start` to `end`.
+  Level { start: [Real; 3], end: [Real; 3] },
+  /// Force and torque of the fluids on the body with this index in
+  /// [`Scene::bodies`], through its colliders sampled as boundaries.
+  Body { body: usize },
+}
+
+impl Gauge {
+  pub fn probe(&self, bodies: &[RigidBodyHandle]) -> io::Result<probe::Probe> {
+    Ok(match *self {
+      Gauge::Point { position } => {
+        probe::Probe::Point { position: position.into() }
+      }
+      Gauge::Flux { position, half_extents } => {
+        probe::Probe::Flux { pose: position.isometry(), half_extents }
+      }
+      Gauge::Volume { position, half_extents } => probe::Probe::Volume {
+        pose: position.isometry(),
+        half_extents: half_extents.into(),
+      },
+      Gauge::Level { start, end } => {
+        probe::Probe::Level { start: start.into(), end: end.into() }
+      }
+      Gauge::Body { body } => probe::Probe::Body {
+        body: *bodies
+          .get(body)
+          .ok_or_else(|| invalid(format!("unknown body {body}")))?,
+      },
+    })
+  }
+}
+
+fn invalid(
+  error: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+impl Scene {
+  /// Loads a scene, resolving the meshes relative to its directory.
+  pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+    let path = path.as_ref();
+    let mut scene = Self::parse(&fs::read_to_string(path)?)?;
+    if let Some(dir) = path.parent() {
+      scene.resolve(dir);
+    }
+    Ok(scene)
+  }
+
+  pub fn parse(source: &str) -> io::Result<Self> {
+    ron::from_str(source).map_err(invalid)
+  }
+
+  fn resolve(&mut self, dir: &Path) {
+    let colliders = self.bodies.iter_mut().flat_map(|body| &mut body.colliders);
+    for collider in colliders {
+      collider.shape.resolve(dir);
+    }
+    for fluid in &mut self.fluids {
+      match &mut fluid.volume {
+        Volume::Shape(shape) | Volume::Fill { container: shape, .. } => {
+          shape.resolve(dir)
+        }
+        Volume::Cube { .. } | Volume::Packed { .. } => {}
+      }
+    }
+    for emitter in &mut self.emitters {
+      emitter.shape.resolve(dir);
+    }
+    for sink in &mut self.sinks {
+      if let Region::Shape { shape, .. } = &mut sink.region {
+        shape.resolve(dir);
+      }
+    }
+  }
+
+  /// Builds the harness with its fluids, the inflows and the outflows of the
+  /// scene.
+  pub fn build(&self) -> io::Result<Stand> {
+    let radius = self.particle_radius;
+    let mut bodies = RigidBodySet::new();
+    let mut colliders = ColliderSet::new();
+    let mut fluids =
+      Fluids::from_pipeline(FluidsPipeline::new(radius, self.smoothing_factor));
+    fluids.set_capture_level(self.capture);
+    let mut body_handles = Vec::new();
+    let mut meshes = Meshes::default();
+
+    for body in &self.bodies {
+      let kind = match body.kind {
+        BodyKind::Fixed => RigidBodyType::Fixed,
+        BodyKind::Dynamic => RigidBodyType::Dynamic,
+        BodyKind::Kinematic => RigidBodyType::KinematicPositionBased,
+      };
+      let handle = bodies.insert(
+        RigidBodyBuilder::new(kind)
+          .position(body.position.isometry())
+          .linvel(body.linvel.into())
+          .build(),
+      );
+      body_handles.push(handle);
+
+      for collider in &body.colliders {
+        let co = ColliderBuilder::new(collider.shape.shared(&mut meshes)?)
+          .position(collider.position.isometry())
+          .density(collider.density)
+          .friction(collider.friction)
+          .restitution(collider.restitution)
+          .build();
+        let co_handle = colliders.insert_with_parent(co, handle, &mut bodies);
+
+        let sampling = match collider.boundary {
+          Some(Sampling::Static) => harness::Sampling::Points(
+            collider.shape.boundary(radius, &mut meshes)?,
+          ),
+          Some(Sampling::Dynamic) => harness::Sampling::Dynamic,
+          None => continue,
+        };
+        fluids
+          .couple(&colliders, co_handle, sampling, InteractionGroups::default())
+          .ok_or_else(|| {
+            invalid(format!("cannot sample {:?}", collider.shape))
+          })?;
+      }
+    }
+
+    let mut handles = HashMap::new();
+    for (seed, fluid) in self.fluids.iter().enumerate() {
+      if handles.contains_key(fluid.name.as_str()) {
+        return Err(invalid(format!("duplicate fluid `{}`", fluid.name)));
+      }
+      let mut built = match &fluid.volume {
+        &Volume::Cube { size: [ni, nj, nk] } => {
+          helper::cube_fluid(ni, nj, nk, radius, fluid.density)
+        }
+        Volume::Shape(shape) => helper::shape_fluid(
+          &*shape.shared(&mut meshes)?,
+          radius,
+          fluid.density,
+        )
+        .ok_or_else(|| invalid(format!("cannot sample {shape:?}")))?,
+        &Volume::Packed { half_extents } => {
+          helper::hcp_fluid(half_extents.into(), radius, fluid.density)
+        }
+        Volume::Fill { container, level } => helper::fill_fluid(
+          &*container.shared(&mut meshes)?,
+          *level,
+          radius,
+          fluid.density,
+        ),
+      };
+      if fluid.jitter > 0.0 {
+        built = helper::jittered(built, fluid.jitter * radius, seed as u64);
+      }
+      let pose = fluid.position.isometry();
+      built.transform_by(&pose);
+      let (linvel, angvel) =
+        (Vector::from(fluid.velocity), Vector::from(fluid.angular_velocity));
+      let center = pose.translation.vector;
+      built = helper::with_velocity_field(built, |point| {
+        linvel + angvel.cross(&(point.coords - center))
+      });
+      built.nonpressure_forces.extend(fluid.forces.iter().map(|f| f.boxed()));
+
+      let handle = fluids.pipeline.liquid_world.add_fluid(built);
+      handles.insert(fluid.name.as_str(), handle);
+    }
+
+    let fluid = |name: &str| {
+      handles
+        .get(name)
+        .copied()
+        .ok_or_else(|| invalid(format!("unknown fluid `{name}`")))
+    };
+
+    let inflows = self
+      .emitters
+      .iter()
+      .enumerate()
+      .map(|(seed, emitter)| {
+        let handle = fluid(&emitter.fluid)?;
+        let pose =
+          Isometry3::new(emitter.center.into(), emitter.rotation.into());
+        let shape = emitter.shape.shared(&mut meshes)?;
+        let flow = if emitter.layered {
+          ShapeFlow::nozzle(pose, &*shape, radius)
+        } else {
+          ShapeFlow::new(pose, &*shape, radius)
+        };
+        let flow = flow
+          .ok_or_else(|| invalid(format!("cannot sample {:?}", emitter.shape)))?
+          .with_velocity(emitter.velocity.into())
+          .with_profile(emitter.profile)
+          .with_jitter(emitter.jitter, seed as u64);
+
+        let mut inflow = Inflow::new(flow, handle)
+          .with_rate(emitter.rate)
+          .with_schedule(emitter.schedule);
+        if let Some(budget) = emitter.budget {
+          inflow = inflow.with_budget(budget);
+        }
+        if let Some(index) = emitter.body {
+          let body = *body_handles
+            .get(index)
+            .ok_or_else(|| invalid(format!("unknown body {index}")))?;
+          inflow = inflow.attached_to(body, Isometry3::identity());
+        }
+        Ok(inflow)
+      })
+      .collect::<io::Result<_>>()?;
+
+    let outflows = self
+      .sinks
+      .iter()
+      .map(|sink| {
+        let built = match &sink.region {
+          Region::Shape { shape, position } => {
+            ShapeSink::new(position.isometry(), shape.shared(&mut meshes)?)
+          }
+          &Region::Outside { mins, maxs } => {
+            ShapeSink::outside(Aabb::new(mins.into(), maxs.into()))
+          }
+        };
+        if sink.fluids.is_empty() {
+          return Ok(Outflow::new(built));
+        }
+        let fluids = sink
+          .fluids
+          .iter()
+          .map(|name| fluid(name))
+          .collect::<io::Result<_>>()?;
+        Ok(Outflow::new(built.with_fluids(fluids)))
+      })
+      .collect::<io::Result<_>>()?;
+
+    let mut harness = Harness::new_empty();
+    harness.set_world(
+      bodies,
+      colliders,
+      ImpulseJointSet::new(),
+      MultibodyJointSet::new(),
+    );
+    harness.physics.gravity = self.gravity.into();
+
+    let params = harness.integration_parameters_mut();
+    params.dt = self.integration.dt;
+    if let Some(iterations) =
+      NonZeroUsize::new(self.integration.solver_iterations)
+    {
+      params.num_solver_iterations = iterations;
+    }
+
+    let mut names = HashSet::new();
+    let probes = self
+      .probes
+      .iter()
+      .map(|probe| {
+        // Names become the file names of the written series.
+        let name = probe.name.as_str();
+        if name.trim_matches('.').is_empty()
+          || name.contains(['/', '\\'])
+          || name.contains("..")
+        {
+          return Err(invalid(format!("invalid probe name `{name}`")));
+        }
+        if !names.insert(name) {
+          return Err(invalid(format!("duplicate probe `{name}`")));
+        }
+        let series = fluids.add_probe(probe.gauge.probe(&body_handles)?);
+        Ok((probe.name.clone(), series))
+      })
+      .collect::<io::Result<_>>()?;
+
+    harness.add_plugin(fluids);
+    Ok(Stand {
+      harness,
+      inflows,
+      outflows,
+      probes: Probes(probes),
+      surface: self.surface,
+      max_steps: None,
+    })
+  }
+}
@@ -1,20 +1,27 @@
 use {
   crate::prelude::*,
   harness::{Harness, PhysicsState},
+  na::UnitQuaternion,
   rapier::{
     dynamics::{
       ImpulseJointSet, IslandManager, MultibodyJointSet, RigidBodySet,
     },
     geometry::{ColliderSet, DefaultBroadPhase, NarrowPhase},
+    math::{Isometry, Point, Vector},
+    parry::shape::{Shape, TypedShape},
   },
+  serde::{Deserialize, Serialize},
 };
 
 pub trait Snapshot {
   fn draw(&self, graphics: &mut Gizmos);
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct PhysicsSnapshot {
   pub timestep_id: usize,
+  pub time: Real,
+  pub dt: Real,
   pub broad_phase: DefaultBroadPhase,
   pub narrow_phase: NarrowPhase,
   pub island_manager: IslandManager,
@@ -38,6 +45,8 @@ impl PhysicsSnapshot {
     } = &harness.physics;
     Self {
       timestep_id: harness.state.timestep_id,
+      time: harness.state.time,
+      dt: harness.delta(),
       island_manager: islands.clone(),
       broad_phase: broad_phase.clone(),
       narrow_phase: narrow_phase.clone(),
@@ -47,27 +56,163 @@ impl PhysicsSnapshot {
       multibody_joints: multibody_joints.clone(),
     }
   }
+
+  /// Draws the solver contacts of the narrow phase with their normals.
+  pub fn draw_contacts(&self, graphics: &mut Gizmos) {
+    let contact = Color::srgb(1.0, 1.0, 0.2);
+    for pair in self.narrow_phase.contact_pairs() {
+      for manifold in &pair.manifolds {
+        let normal = vec3(&manifold.data.normal);
+        for point in &manifold.data.solver_contacts {
+          let point = vec3(&point.point.coords);
+          graphics.sphere(Isometry3d::from_translation(point), 0.02, contact);
+          graphics.line(point, point + normal * 0.1, contact);
+        }
+      }
+    }
+  }
 }
 
 impl Snapshot for PhysicsSnapshot {
   fn draw(&self, graphics: &mut Gizmos) {
-    for (_, body) in self.bodies.iter() {
-      for &handle in body.colliders() {
-        if let Some(collider) = self.colliders.get(handle)
-          && let Some(cuboid) = collider.shape().as_cuboid()
-        {
-          let pos = body.translation();
-          let rot = body.rotation();
-          graphics.cuboid(
-            Transform {
-              translation: Vec3::from_slice(pos.as_slice()),
-              rotation: Quat::from_slice(rot.coords.as_slice()),
-              scale: Vec3::from_slice(cuboid.half_extents.as_slice()),
-            },
-            Color::srgb(1.0, 0.0, 0.0),
-          );
-        }
+    for (_, collider) in self.colliders.iter() {
+      let color = match collider.parent().and_then(|h| self.bodies.get(h)) {
+        Some(body) if body.is_dynamic() => Color::srgb(1.0, 0.55, 0.1),
+        Some(body) if body.is_kinematic() => Color::srgb(0.2, 0.5, 1.0),
+        _ => Color::srgb(0.6, 0.6, 0.6),
+      };
+      draw_shape(graphics, collider.shape(), collider.position(), color);
+    }
+
+    let anchor = Color::srgb(0.2, 1.0, 0.4);
+    for (_, joint) in self.impulse_joints.iter() {
+      let (Some(body1), Some(body2)) =
+        (self.bodies.get(joint.body1), self.bodies.get(joint.body2))
+      else {
+        continue;
+      };
+      let a = body1.position() * joint.data.local_anchor1();
+      let b = body2.position() * joint.data.local_anchor2();
+      draw_joint(graphics, &a, &b, anchor);
+    }
+    for (_, _, multibody, link) in self.multibody_joints.iter() {
+      let Some(parent) = link.parent_id().and_then(|id| multibody.link(id))
+      else {
+        continue;
+      };
+      let data = &link.joint().data;
+      let a = parent.local_to_world() * data.local_anchor1();
+      let b = link.local_to_world() * data.local_anchor2();
+      draw_joint(graphics, &a, &b, anchor);
+    }
+  }
+}
+
+fn vec3(vector: &Vector<Real>) -> Vec3 {
+  Vec3::from_slice(vector.as_slice())
+}
+
+/// The gizmo isometry of a physics one.
+pub fn isometry(isometry: &Isometry<Real>) -> Isometry3d {
+  Isometry3d::new(
+    vec3(&isometry.translation.vector),
+    Quat::from_slice(isometry.rotation.coords.as_slice()),
+  )
+}
+
+/// Draws the wireframe of `shape` placed at `pose`, recursing into compound
+/// parts.
+fn draw_shape(
+  graphics: &mut Gizmos,
+  shape: &dyn Shape,
+  pose: &Isometry<Real>,
+  color: Color,
+) {
+  match shape.as_typed_shape() {
+    TypedShape::Ball(ball) => {
+      graphics.sphere(isometry(pose), ball.radius, color);
+    }
+    TypedShape::Cuboid(cuboid) => {
+      let Isometry3d { translation, rotation } = isometry(pose);
+      graphics.cuboid(
+        Transform {
+          translation: translation.into(),
+          rotation,
+          scale: vec3(&cuboid.half_extents) * 2.0,
+        },
+        color,
+      );
+    }
+    TypedShape::Capsule(capsule) => {
+      let axis = capsule.segment.b - capsule.segment.a;
+      let rotation = UnitQuaternion::rotation_between(&Vector::y(), &axis)
+        .unwrap_or_else(UnitQuaternion::identity);
+      let local =
+        Isometry::from_parts(capsule.center().coords.into(), rotation);
+      graphics.primitive_3d(
+        &Capsule3d::new(capsule.radius, capsule.height()),
+        isometry(&(pose * local)),
+        color,
+      );
+    }
+    TypedShape::Cylinder(cylinder) => {
+      graphics.primitive_3d(
+        &Cylinder::new(cylinder.radius, 2.0 * cylinder.half_height),
+        isometry(pose),
+        color,
+      );
+    }
+    TypedShape::Cone(cone) => {
+      graphics.primitive_3d(
+        &Cone::new(cone.radius, 2.0 * cone.half_height),
+        isometry(pose),
+        color,
+      );
+    }
+    TypedShape::TriMesh(trimesh) => {
+      draw_triangles(
+        graphics,
+        trimesh.vertices(),
+        trimesh.indices(),
+        pose,
+        color,
+      );
+    }
+    TypedShape::ConvexPolyhedron(polyhedron) => {
+      let (vertices, indices) = polyhedron.to_trimesh();
+      draw_triangles(graphics, &vertices, &indices, pose, color);
+    }
+    TypedShape::Compound(compound) => {
+      for (part_pose, part) in compound.shapes() {
+        draw_shape(graphics, &**part, &(pose * part_pose), color);
       }
     }
+    _ => {}
   }
 }
+
+fn draw_triangles(
+  graphics: &mut Gizmos,
+  vertices: &[Point<Real>],
+  indices: &[[u32; 3]],
+  pose: &Isometry<Real>,
+  color: Color,
+) {
+  for &[a, b, c] in indices {
+    let [a, b, c] =
+      [a, b, c].map(|i| vec3(&(pose * vertices[i as usize]).coords));
+    graphics.linestrip([a, b, c, a], color);
+  }
+}
+
+fn draw_joint(
+  graphics: &mut Gizmos,
+  a: &Point<Real>,
+  b: &Point<Real>,
+  color: Color,
+) {
+  let (a, b) = (vec3(&a.coords), vec3(&b.coords));
+  graphics.line(a, b, color);
+  graphics.sphere(Isometry3d::from_translation(a), 0.05, color);
+  graphics.sphere(Isometry3d::from_translation(b), 0.05, color);
+}
@@ -0,0 +1,93 @@
+use {
+  crate::prelude::*,
+  rapier::math::{Point, Vector},
+  std::{collections::HashMap, f32::consts::PI},
+};
+
+/// The cubic spline kernel of radius `h`, the default kernel of salva.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CubicSpline {
+  pub h: Real,
+}
+
+impl CubicSpline {
+  pub fn new(h: Real) -> Self {
+    Self { h }
+  }
+
+  fn normalization(&self) -> Real {
+    8.0 / (PI * self.h.powi(3))
+  }
+
+  pub fn value(&self, r: Real) -> Real {
+    let q = r / self.h;
+    let value = if q <= 0.5 {
+      6.0 * (q.powi(3) - q.powi(2)) + 1.0
+    } else if q <= 1.0 {
+      2.0 * (1.0 - q).powi(3)
+    } else {
+      0.0
+    };
+    self.normalization() * value
+  }
+
+  /// Gradient of the kernel at `r`, the vector from the neighbour to the
+  /// particle.
+  pub fn gradient(&self, r: &Vector<Real>) -> Vector<Real> {
+    let norm = r.norm();
+    let q = norm / self.h;
+    if norm <= Real::EPSILON || q > 1.0 {
+      return Vector::zeros();
+    }
+    let derivative = if q <= 0.5 {
+      6.0 * (3.0 * q.powi(2) - 2.0 * q)
+    } else {
+      -6.0 * (1.0 - q).powi(2)
+    };
+    r * (self.normalization() / self.h * derivative / norm)
+  }
+}
+
+/// Buckets points into cubic cells, answering neighbourhood queries of a
+/// radius up to the cell size.
+pub struct Grid<'a> {
+  points: &'a [Point<Real>],
+  cell: Real,
+  cells: HashMap<[i32; 3], Vec<usize>>,
+}
+
+impl<'a> Grid<'a> {
+  pub fn new(points: &'a [Point<Real>], cell: Real) -> Self {
+    let mut grid = Self { points, cell, cells: HashMap::new() };
+    for (i, point) in points.iter().enumerate() {
+      grid.cells.entry(grid.key(point)).or_default().push(i);
+    }
+    grid
+  }
+
+  pub fn key(&self, point: &Point<Real>) -> [i32; 3] {
+    (point.coords / self.cell).map(|x| x.floor() as i32).into()
+  }
+
+  pub fn points(&self) -> &'a [Point<Real>] {
+    self.points
+  }
+
+  /// Indices of the points closer than the cell size to `point`, including
+  /// the point itself when it is part of the grid.
+  pub fn neighbours(
+    &self,
+    point: &Point<Real>,
+  ) -> impl Iterator<Item = usize> + '_ {
+    let [x, y, z] = self.key(point);
+    let point = *point;
+    let cells = (-1..=1).flat_map(move |i| {
+      (-1..=1).flat_map(move |j| (-1..=1).map(move |k| [x + i, y + j, z + k]))
+    });
+    cells.filter_map(move |key| self.cells.get(&key)).flatten().copied().filter(
+      move |&n| {
+        na::distance_squared(&self.points[n], &point) < self.cell.powi(2)
+      },
+    )
+  }
+}
@@ -0,0 +1,230 @@
+use {
+  super::{Playback, Timeline},
+  crate::prelude::*,
+  bevy::{
+    asset::RenderAssetUsages,
+    render::mesh::{Indices, PrimitiveTopology},
+  },
+  na::UnitQuaternion,
+  rapier::{
+    dynamics::RigidBodyHandle,
+    geometry::ColliderHandle,
+    math::{Isometry, Point, Vector},
+    parry::shape::{Shape, TypedShape},
+  },
+  std::collections::HashMap,
+};
+
+/// The entity showing a rigid body of the played frame.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct BodyView(pub RigidBodyHandle);
+
+/// A child of a [`BodyView`] rendering one of its colliders.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct ColliderView(pub ColliderHandle);
+
+/// The body last clicked, drawn highlighted. Clicking it again clears it.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub struct Selected(pub Option<RigidBodyHandle>);
+
+#[derive(Resource, Default)]
+struct Views {
+  bodies: HashMap<RigidBodyHandle, Entity>,
+  material: Option<Handle<StandardMaterial>>,
+  highlight: Option<Handle<StandardMaterial>>,
+}
+
+pub fn plugin(app: &mut App) {
+  if !app.is_plugin_added::<MeshPickingPlugin>() {
+    app.add_plugins(MeshPickingPlugin);
+  }
+  app
+    .init_resource::<Views>()
+    .init_resource::<Selected>()
+    .add_systems(Update, highlight.after(sync));
+}
+
+pub fn transform(isometry: &Isometry<Real>) -> Transform {
+  Transform {
+    translation: Vec3::from_slice(isometry.translation.vector.as_slice()),
+    rotation: Quat::from_slice(isometry.rotation.coords.as_slice()),
+    scale: Vec3::ONE,
+  }
+}
+
+/// Despawns every view, the next frame spawning them again.
+pub(super) fn clear(world: &mut World) {
+  // Handles of the new world may name other bodies.
+  if let Some(mut selected) = world.get_resource_mut::<Selected>() {
+    selected.0 = None;
+  }
+  let Some(mut views) = world.get_resource_mut::<Views>() else { return };
+  let entities: Vec<_> = views.bodies.drain().map(|(_, e)| e).collect();
+  for entity in entities {
+    world.despawn(entity);
+  }
+}
+
+/// Spawns the bodies of the played frame and moves them to its poses.
+pub fn sync(
+  mut commands: Commands,
+  mut timeline: ResMut<Timeline>,
+  playback: Res<Playback>,
+  mut views: ResMut<Views>,
+  mut transforms: Query<&mut Transform, With<BodyView>>,
+  mut meshes: ResMut<Assets<Mesh>>,
+  mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+  let index = playback.frame(&timeline);
+  let Some((physics, _)) = timeline.get(index) else { return };
+
+  // Bodies missing from an older frame or removed since.
+  views.bodies.retain(|&handle, &mut entity| {
+    let alive = physics.bodies.contains(handle);
+    if !alive {
+      commands.entity(entity).despawn();
+    }
+    alive
+  });
+
+  let material = views
+    .material
+    .get_or_insert_with(|| materials.add(Color::srgb(0.6, 0.6, 0.65)))
+    .clone();
+
+  for (handle, body) in physics.bodies.iter() {
+    let pose = transform(body.position());
+    if let Some(&entity) = views.bodies.get(&handle) {
+      if let Ok(mut transform) = transforms.get_mut(entity) {
+        *transform = pose;
+      }
+      continue;
+    }
+
+    let mut entity = commands.spawn((
+      BodyView(handle),
+      Name::new(format!("{handle:?}")),
+      pose,
+      Visibility::default(),
+    ));
+    // Clicks on the collider meshes bubble up to the body.
+    entity.observe(select);
+    entity.with_children(|parent| {
+      for &co_handle in body.colliders() {
+        let Some(collider) = physics.colliders.get(co_handle) else {
+          continue;
+        };
+        let local = collider.position_wrt_parent().copied().unwrap_or_default();
+        for (offset, mesh) in meshes_of(collider.shape()) {
+          parent.spawn((
+            ColliderView(co_handle),
+            Mesh3d(meshes.add(mesh)),
+            MeshMaterial3d(material.clone()),
+            transform(&(local * offset)),
+          ));
+        }
+      }
+    });
+    views.bodies.insert(handle, entity.id());
+  }
+}
+
+fn select(
+  trigger: Trigger<Pointer<Click>>,
+  views: Query<&BodyView>,
+  mut selected: ResMut<Selected>,
+) {
+  let Ok(&BodyView(handle)) = views.get(trigger.target()) else { return };
+  selected.0 = (selected.0 != Some(handle)).then_some(handle);
+}
+
+/// Draws the colliders of the [`Selected`] body with the highlight material.
+fn highlight(
+  selected: Res<Selected>,
+  mut views: ResMut<Views>,
+  spawned: Query<(), Added<ColliderView>>,
+  bodies: Query<&BodyView>,
+  mut colliders: Query<
+    (&ChildOf, &mut MeshMaterial3d<StandardMaterial>),
+    With<ColliderView>,
+  >,
+  mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+  if !selected.is_changed() && spawned.is_empty() {
+    return;
+  }
+
+  let views = &mut *views;
+  let Some(material) = views.material.clone() else { return };
+  let highlight = views
+    .highlight
+    .get_or_insert_with(|| materials.add(Color::srgb(1.0, 0.75, 0.2)))
+    .clone();
+
+  for (child_of, mut current) in &mut colliders {
+    let body = bodies.get(child_of.parent()).ok().map(|view| view.0);
+    let wanted = if selected.0.is_some() && body == selected.0 {
+      &highlight
+    } else {
+      &material
+    };
+    if current.0 != *wanted {
+      current.0 = wanted.clone();
+    }
+  }
+}
+
+/// Meshes of the parts of `shape` with their local poses, empty for shapes
+/// that are not rendered.
+fn meshes_of(shape: &dyn Shape) -> Vec<(Isometry<Real>, Mesh)> {
+  let at_origin = |mesh: Mesh| vec![(Isometry::identity(), mesh)];
+
+  match shape.as_typed_shape() {
+    TypedShape::Ball(ball) => at_origin(Sphere::new(ball.radius).into()),
+    TypedShape::Cuboid(cuboid) => {
+      let size = Vec3::from_slice(cuboid.half_extents.as_slice()) * 2.0;
+      at_origin(Cuboid::from_size(size).into())
+    }
+    TypedShape::Capsule(capsule) => {
+      let axis = capsule.segment.b - capsule.segment.a;
+      let rotation = UnitQuaternion::rotation_between(&Vector::y(), &axis)
+        .unwrap_or_else(UnitQuaternion::identity);
+      let pose = Isometry::from_parts(capsule.center().coords.into(), rotation);
+      let mesh = Capsule3d::new(capsule.radius, capsule.height());
+      vec![(pose, mesh.into())]
+    }
+    TypedShape::Cylinder(cylinder) => at_origin(
+      Cylinder::new(cylinder.radius, 2.0 * cylinder.half_height).into(),
+    ),
+    TypedShape::Cone(cone) => {
+      at_origin(Cone::new(cone.radius, 2.0 * cone.half_height).into())
+    }
+    TypedShape::TriMesh(trimesh) => {
+      at_origin(triangles(trimesh.vertices(), trimesh.indices()))
+    }
+    TypedShape::ConvexPolyhedron(polyhedron) => {
+      let (vertices, indices) = polyhedron.to_trimesh();
+      at_origin(triangles(&vertices, &indices))
+    }
+    TypedShape::Compound(compound) => compound
+      .shapes()
+      .iter()
+      .flat_map(|(pose, part)| {
+        meshes_of(&**part)
+          .into_iter()
+          .map(move |(offset, mesh)| (pose * offset, mesh))
+      })
+      .collect(),
+    _ => Vec::new(),
+  }
+}
+
+/// A flat shaded mesh of the triangles.
+fn triangles(vertices: &[Point<Real>], indices: &[[u32; 3]]) -> Mesh {
+  let positions: Vec<_> = vertices.iter().map(|p| [p.x, p.y, p.z]).collect();
+  Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_indices(Indices::U32(indices.as_flattened().to_vec()))
+    .with_duplicated_vertices()
+    .with_computed_flat_normals()
+}
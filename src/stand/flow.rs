@@ -1,18 +1,47 @@
 use {
-  parry::{bounding_volume::Aabb, shape::Shape},
+  parry::{
+    bounding_volume::Aabb,
+    query::PointQuery,
+    shape::{Shape, SharedShape},
+  },
+  rand::{Rng, SeedableRng, rngs::StdRng},
   salva::{
     LiquidWorld,
-    math::{Point, Vector},
+    math::{Isometry, Point, Vector},
     object::FluidHandle,
     parry, sampling,
   },
+  serde::{Deserialize, Serialize},
+  std::collections::HashSet,
 };
 
+/// Distribution of the flow velocity over the section of a [`ShapeFlow`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Profile {
+  #[default]
+  Uniform,
+  /// Poiseuille flow, zero at the rim and twice the mean speed on the axis.
+  Parabolic,
+}
+
+/// Emits particles sampled in a shape placed at `pose`, with `velocity`
+/// expressed in the frame of the shape.
 pub struct ShapeFlow {
-  center: Vector<f32>,
+  pose: Isometry<f32>,
   velocity: Vector<f32>,
   samples: Vec<Point<f32>>,
   radius: f32,
+  profile: Profile,
+  /// Random offset of the particles, relative to the radius.
+  jitter: f32,
+  rng: StdRng,
+  layers: Option<Layers>,
+}
+
+/// Nozzle state, emitting one layer per `spacing` travelled by the flow.
+struct Layers {
+  spacing: f32,
+  travelled: f32,
 }
 
 fn ball_aabb(center: Point<f32>, radius: f32) -> Aabb {
@@ -20,13 +49,55 @@ fn ball_aabb(center: Point<f32>, radius: f32) -> Aabb {
 }
 
 impl ShapeFlow {
-  pub fn new<S: Shape>(
-    center: Vector<f32>,
-    shape: &S,
+  fn with_samples(
+    pose: Isometry<f32>,
+    samples: Vec<Point<f32>>,
+    radius: f32,
+  ) -> Self {
+    Self {
+      pose,
+      velocity: Vector::zeros(),
+      samples,
+      radius,
+      profile: Profile::Uniform,
+      jitter: 0.0,
+      rng: StdRng::seed_from_u64(0),
+      layers: None,
+    }
+  }
+
+  /// Emits the whole volume of `shape` at once.
+  pub fn new(
+    pose: Isometry<f32>,
+    shape: &dyn Shape,
     radius: f32,
   ) -> Option<Self> {
     let samples = sampling::shape_volume_ray_sample(shape, radius)?;
-    Some(Self { center, samples, radius, velocity: Default::default() })
+    Some(Self::with_samples(pose, samples, radius))
+  }
+
+  /// Emits the section of `shape` in the local `xz` plane one particle layer
+  /// each time the flow travelled one particle spacing, so jets stay
+  /// continuous. The velocity is expected to leave that plane.
+  pub fn nozzle(
+    pose: Isometry<f32>,
+    shape: &dyn Shape,
+    radius: f32,
+  ) -> Option<Self> {
+    let spacing = 2.0 * radius;
+    let mut samples = sampling::shape_volume_ray_sample(shape, radius)?;
+    for sample in &mut samples {
+      sample.x = (sample.x / spacing).round() * spacing;
+      sample.y = 0.0;
+      sample.z = (sample.z / spacing).round() * spacing;
+    }
+    samples.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.z.total_cmp(&b.z)));
+    samples.dedup();
+
+    let mut flow = Self::with_samples(pose, samples, radius);
+    // The first layer leaves with the first step.
+    flow.layers = Some(Layers { spacing, travelled: spacing });
+    Some(flow)
   }
 
   pub fn with_velocity(mut self, velocity: Vector<f32>) -> Self {
@@ -34,25 +105,211 @@ impl ShapeFlow {
     self
   }
 
-  pub fn emit(&self, world: &mut LiquidWorld, handle: FluidHandle) -> bool {
-    let particles =
-      self.samples.iter().map(|&sample| sample + self.center).flat_map(
-        |sample| {
-          let aabb = ball_aabb(sample, self.radius);
-          if world.particles_intersecting_aabb(aabb).count() == 0 {
-            Some(sample)
-          } else {
-            None
+  pub fn with_profile(mut self, profile: Profile) -> Self {
+    self.profile = profile;
+    self
+  }
+
+  /// Offsets each particle by up to `jitter` radii along every axis, using a
+  /// generator seeded with `seed`.
+  pub fn with_jitter(mut self, jitter: f32, seed: u64) -> Self {
+    self.jitter = jitter;
+    self.rng = StdRng::seed_from_u64(seed);
+    self
+  }
+
+  pub fn radius(&self) -> f32 {
+    self.radius
+  }
+
+  /// Amount of particles emitted into an empty neighbourhood.
+  pub fn capacity(&self) -> usize {
+    self.samples.len()
+  }
+
+  /// Emits at most `max` particles into the free samples, spread evenly over
+  /// the shape, returning how many were added. Nozzles advance their layers
+  /// by a step of `dt`.
+  pub fn emit(
+    &mut self,
+    world: &mut LiquidWorld,
+    handle: FluidHandle,
+    max: usize,
+    dt: f32,
+  ) -> usize {
+    self.emit_moving(world, handle, max, dt, &Motion::default())
+  }
+
+  /// Like [`emit`](Self::emit) with the shape and the velocity expressed in
+  /// the frame of `motion`, whose point velocity is added to the particles.
+  pub fn emit_moving(
+    &mut self,
+    world: &mut LiquidWorld,
+    handle: FluidHandle,
+    max: usize,
+    dt: f32,
+    motion: &Motion,
+  ) -> usize {
+    let speed = self.velocity.norm();
+    let axis =
+      self.velocity.try_normalize(f32::EPSILON).unwrap_or_else(Vector::zeros);
+
+    // Distances travelled by the layers leaving during this step.
+    let offsets = match &mut self.layers {
+      None => vec![0.0],
+      Some(layers) => {
+        layers.travelled += speed * dt;
+        let mut offsets = Vec::new();
+        while layers.travelled >= layers.spacing {
+          layers.travelled -= layers.spacing;
+          offsets.push(layers.travelled);
+        }
+        offsets
+      }
+    };
+
+    let rim = self
+      .samples
+      .iter()
+      .map(|sample| radial(sample, &axis))
+      .fold(0.0, f32::max)
+      + self.radius;
+    let pose = motion.pose * self.pose;
+
+    let mut free = Vec::new();
+    for offset in offsets {
+      for sample in &self.samples {
+        let jitter = self.radius
+          * self.jitter
+          * Vector::from_fn(|_, _| self.rng.gen_range(-1.0..=1.0));
+        let point = pose * (sample + axis * offset + jitter);
+
+        let aabb = ball_aabb(point, self.radius);
+        if world.particles_intersecting_aabb(aabb).count() != 0 {
+          continue;
+        }
+
+        let scale = match self.profile {
+          Profile::Uniform => 1.0,
+          Profile::Parabolic => {
+            2.0 * (1.0 - (radial(sample, &axis) / rim).powi(2)).max(0.0)
           }
-        },
-      );
+        };
+        let velocity =
+          pose.rotation * (self.velocity * scale) + motion.velocity_at(&point);
+        free.push((point, velocity));
+      }
+    }
 
-    let particles: Vec<_> = particles.collect();
-    let Some(fluid) = world.fluids_mut().get_mut(handle) else { return false };
+    let count = max.min(free.len());
+    if count == 0 {
+      return 0;
+    }
+    let (particles, velocities): (Vec<_>, Vec<_>) =
+      (0..count).map(|i| free[i * free.len() / count]).unzip();
 
-    let velocities: Vec<_> = particles.iter().map(|_| self.velocity).collect();
+    let Some(fluid) = world.fluids_mut().get_mut(handle) else { return 0 };
     fluid.add_particles(&particles, Some(&velocities));
 
-    true
+    count
+  }
+}
+
+/// Distance of `point` to the line through the origin along `axis`.
+fn radial(point: &Point<f32>, axis: &Vector<f32>) -> f32 {
+  (point.coords - axis * point.coords.dot(axis)).norm()
+}
+
+/// A rigid frame an emitter moves with.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Motion {
+  pub pose: Isometry<f32>,
+  pub linvel: Vector<f32>,
+  pub angvel: Vector<f32>,
+  /// The point `angvel` rotates around, in world space.
+  pub center: Point<f32>,
+}
+
+impl Motion {
+  pub fn velocity_at(&self, point: &Point<f32>) -> Vector<f32> {
+    self.linvel + self.angvel.cross(&(point - self.center))
+  }
+}
+
+/// Where a [`ShapeSink`] removes particles.
+#[derive(Clone)]
+pub enum Region {
+  /// Particles inside `shape` placed at the isometry.
+  Inside(Isometry<f32>, SharedShape),
+  /// Particles that left the domain.
+  Outside(Aabb),
+}
+
+impl Region {
+  pub fn contains(&self, point: &Point<f32>) -> bool {
+    match self {
+      Region::Inside(position, shape) => shape.contains_point(position, point),
+      Region::Outside(aabb) => !aabb.contains_local_point(point),
+    }
+  }
+}
+
+/// The counterpart of [`ShapeFlow`], deleting the particles that enter its
+/// region.
+pub struct ShapeSink {
+  region: Region,
+  /// Fluids drained by the sink, every fluid when `None`.
+  fluids: Option<Vec<FluidHandle>>,
+}
+
+impl ShapeSink {
+  pub fn new(position: Isometry<f32>, shape: SharedShape) -> Self {
+    Self { region: Region::Inside(position, shape), fluids: None }
+  }
+
+  /// Drains the particles leaving `domain`.
+  pub fn outside(domain: Aabb) -> Self {
+    Self { region: Region::Outside(domain), fluids: None }
+  }
+
+  pub fn with_fluids(mut self, fluids: Vec<FluidHandle>) -> Self {
+    self.fluids = Some(fluids);
+    self
+  }
+
+  pub fn region(&self) -> &Region {
+    &self.region
+  }
+
+  /// Marks the particles inside the region for deletion at the next step,
+  /// returning their amount. Particles already in `marked`, by another sink
+  /// during the same step, are skipped and the new ones added to it.
+  pub fn drain(
+    &self,
+    world: &mut LiquidWorld,
+    marked: &mut HashSet<(FluidHandle, usize)>,
+  ) -> usize {
+    let mut removed = 0;
+    for (handle, fluid) in world.fluids_mut().iter_mut() {
+      if let Some(fluids) = &self.fluids
+        && !fluids.contains(&handle)
+      {
+        continue;
+      }
+
+      let inside: Vec<_> = fluid
+        .positions
+        .iter()
+        .enumerate()
+        .filter(|(_, point)| self.region.contains(point))
+        .map(|(i, _)| i)
+        .filter(|&i| marked.insert((handle, i)))
+        .collect();
+      for &i in &inside {
+        fluid.delete_particle_at_next_timestep(i);
+      }
+      removed += inside.len();
+    }
+    removed
   }
 }
@@ -0,0 +1,196 @@
+use {
+  super::{FluidState, FrameCell, Outflow, Stand, Timeline, TimelineConfig},
+  crate::{
+    export::Exporter,
+    harness::{Fluids, Harness},
+    prelude::*,
+    probe::Probes,
+  },
+  std::time::{Duration, Instant},
+};
+
+/// Progress of a [`Headless`] run, passed to the progress callback.
+#[derive(Debug, Clone, Copy)]
+pub struct Progress {
+  pub timestep_id: usize,
+  /// Total number of steps when running a fixed amount of steps.
+  pub total: Option<usize>,
+  pub time: f32,
+  pub particles: usize,
+  /// Particles removed by every outflow since the start.
+  pub removed: usize,
+  pub elapsed: Duration,
+}
+
+type ProgressCallback = Box<dyn FnMut(&Progress, &Harness)>;
+
+/// Steps a [`Harness`] together with its plugins outside of any window,
+/// using the same systems as [`plugin`](super::plugin).
+pub struct Headless {
+  app: SubApp,
+  timeline: Option<Timeline>,
+  exporter: Option<Exporter>,
+  report_every: usize,
+  report: ProgressCallback,
+}
+
+impl Headless {
+  pub fn new(stand: Stand) -> Self {
+    let mut app = super::sub_app(stand);
+    app.world_mut().resource_mut::<FluidState>().record = false;
+    Self {
+      app,
+      timeline: None,
+      exporter: None,
+      report_every: 0,
+      report: Box::new(log_progress),
+    }
+  }
+
+  /// Reports progress every `every` steps, `0` disables reporting.
+  pub fn with_progress(mut self, every: usize) -> Self {
+    self.report_every = every;
+    self
+  }
+
+  /// Replaces the default logging progress reporter.
+  pub fn on_progress(
+    mut self,
+    report: impl FnMut(&Progress, &Harness) + 'static,
+  ) -> Self {
+    self.report = Box::new(report);
+    self
+  }
+
+  /// Captures the steps into a [`Timeline`] recorded with `config`.
+  pub fn with_timeline(mut self, config: TimelineConfig) -> Self {
+    let mut state = self.app.world_mut().resource_mut::<FluidState>();
+    state.record = true;
+    state.every = config.decimation;
+    self.timeline = Some(Timeline::with_config(config));
+    self
+  }
+
+  /// Only captures every `every`-th step, for the timeline and the exporter.
+  pub fn with_decimation(mut self, every: usize) -> Self {
+    self.app.world_mut().resource_mut::<FluidState>().every = every;
+    self
+  }
+
+  /// Writes every captured step to disk with `exporter`.
+  pub fn with_exporter(mut self, exporter: Exporter) -> Self {
+    self.app.world_mut().resource_mut::<FluidState>().record = true;
+    self.exporter = Some(exporter);
+    self
+  }
+
+  pub fn harness(&self) -> &Harness {
+    self.app.world().non_send_resource::<Harness>()
+  }
+
+  pub fn fluids(&self) -> Option<&Fluids> {
+    self.harness().plugin()
+  }
+
+  pub fn outflows(&mut self) -> Vec<&Outflow> {
+    let mut query = self.app.world_mut().query::<&Outflow>();
+    query.iter(self.app.world()).collect()
+  }
+
+  pub fn probes(&self) -> &Probes {
+    self.app.world().resource::<Probes>()
+  }
+
+  pub fn timeline(&self) -> Option<&Timeline> {
+    self.timeline.as_ref()
+  }
+
+  pub fn into_timeline(self) -> Option<Timeline> {
+    self.timeline
+  }
+
+  /// Steps once, returning `false` when the step limit of the stand stopped
+  /// the harness instead.
+  pub fn step(&mut self) -> bool {
+    let timestep_id = self.harness().state.timestep_id;
+    self.app.update();
+
+    if let Some(FrameCell(frame)) =
+      self.app.world_mut().remove_resource::<FrameCell>()
+    {
+      if let Some(exporter) = &self.exporter
+        && let Err(err) = exporter.export(&frame)
+      {
+        error!("failed to export timestep {}: {err}", frame.0.timestep_id);
+      }
+      if let Some(timeline) = &mut self.timeline {
+        timeline.push(frame);
+      }
+    }
+    self.harness().state.timestep_id != timestep_id
+  }
+
+  /// Runs `steps` steps, fewer when the step limit is reached first.
+  pub fn run(&mut self, steps: usize) {
+    let start = Instant::now();
+    for _ in 0..steps {
+      if !self.step() {
+        break;
+      }
+      self.report(start, Some(steps));
+    }
+  }
+
+  /// Steps until `predicate` returns `true` or the step limit is reached,
+  /// returning the amount of steps.
+  pub fn run_until(
+    &mut self,
+    mut predicate: impl FnMut(&Harness) -> bool,
+  ) -> usize {
+    let start = Instant::now();
+    let mut steps = 0;
+    while !predicate(self.harness()) && self.step() {
+      self.report(start, None);
+      steps += 1;
+    }
+    steps
+  }
+
+  fn report(&mut self, start: Instant, total: Option<usize>) {
+    let timestep_id = self.harness().state.timestep_id;
+    if self.report_every == 0 || timestep_id % self.report_every != 0 {
+      return;
+    }
+
+    let removed = self.outflows().iter().map(|outflow| outflow.total).sum();
+    let harness = self.app.world().non_send_resource::<Harness>();
+
+    let progress = Progress {
+      timestep_id,
+      total,
+      time: harness.state.time,
+      particles: harness.plugin::<Fluids>().map_or(0, |fluids| {
+        let world = &fluids.pipeline.liquid_world;
+        world.fluids().iter().map(|(_, fluid)| fluid.positions.len()).sum()
+      }),
+      removed,
+      elapsed: start.elapsed(),
+    };
+    (self.report)(&progress, harness);
+  }
+}
+
+fn log_progress(progress: &Progress, harness: &Harness) {
+  let Progress { timestep_id, total, time, particles, removed, elapsed } =
+    *progress;
+  let step = match total {
+    Some(total) => format!("{timestep_id}/{total}"),
+    None => format!("{timestep_id}"),
+  };
+  info!(
+    "step {step} t={time:.3}s particles={particles} removed={removed} \
+     elapsed={:.1}s {}",
+    elapsed.as_secs_f32(),
+    harness.profiling_string(),
+  );
+}
@@ -0,0 +1,175 @@
+use {
+  crate::{
+    color::{ColorMode, Coloring, Range},
+    prelude::*,
+  },
+  bevy::ui::Val::Px,
+};
+
+/// Amount of color swatches making up the bar of the legend.
+const SWATCHES: usize = 32;
+
+/// Key bindings cycling the [`Coloring`] of the particles.
+#[derive(Resource, Debug, Clone)]
+pub struct ColoringKeys {
+  pub mode: KeyCode,
+  pub colormap: KeyCode,
+  /// Freezes the current bounds, or goes back to the bounds of each frame.
+  pub range: KeyCode,
+  pub legend: KeyCode,
+}
+
+impl Default for ColoringKeys {
+  fn default() -> Self {
+    Self {
+      mode: KeyCode::KeyC,
+      colormap: KeyCode::KeyV,
+      range: KeyCode::KeyX,
+      legend: KeyCode::KeyH,
+    }
+  }
+}
+
+/// The on-screen legend of the particle colors.
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct Legend {
+  pub visible: bool,
+  /// The values at the ends of the colormap in the drawn frame.
+  pub bounds: (Real, Real),
+}
+
+impl Default for Legend {
+  fn default() -> Self {
+    Self { visible: true, bounds: (0.0, 1.0) }
+  }
+}
+
+#[derive(Component)]
+struct Root;
+
+#[derive(Component)]
+enum Field {
+  Title,
+  Min,
+  Max,
+}
+
+#[derive(Component)]
+struct Swatch(usize);
+
+pub fn plugin(app: &mut App) {
+  app
+    .init_resource::<Coloring>()
+    .init_resource::<ColoringKeys>()
+    .init_resource::<Legend>()
+    .add_systems(Startup, spawn)
+    .add_systems(PostUpdate, refresh);
+}
+
+fn spawn(mut commands: Commands) {
+  let font = TextFont { font_size: 14.0, ..default() };
+  commands
+    .spawn((
+      Root,
+      Node {
+        position_type: PositionType::Absolute,
+        left: Px(12.0),
+        bottom: Px(12.0),
+        flex_direction: FlexDirection::Column,
+        row_gap: Px(4.0),
+        padding: UiRect::all(Px(8.0)),
+        ..default()
+      },
+      BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+    ))
+    .with_children(|root| {
+      root.spawn((Field::Title, Text::default(), font.clone()));
+      root
+        .spawn(Node { width: Px(200.0), height: Px(12.0), ..default() })
+        .with_children(|bar| {
+          for i in 0..SWATCHES {
+            bar.spawn((
+              Swatch(i),
+              Node { flex_grow: 1.0, ..default() },
+              BackgroundColor::default(),
+            ));
+          }
+        });
+      root
+        .spawn(Node {
+          justify_content: JustifyContent::SpaceBetween,
+          ..default()
+        })
+        .with_children(|labels| {
+          labels.spawn((Field::Min, Text::default(), font.clone()));
+          labels.spawn((Field::Max, Text::default(), font));
+        });
+    });
+}
+
+/// Cycles the coloring with the [`ColoringKeys`].
+pub fn update(
+  keys: Res<ColoringKeys>,
+  input: Res<ButtonInput<KeyCode>>,
+  mut coloring: ResMut<Coloring>,
+  mut legend: ResMut<Legend>,
+) {
+  let pressed = |key| input.just_pressed(key);
+
+  if pressed(keys.mode) {
+    coloring.mode = coloring.mode.next();
+  }
+  if pressed(keys.colormap) {
+    coloring.colormap = coloring.colormap.next();
+  }
+  if pressed(keys.range) {
+    coloring.range = match coloring.range {
+      Range::Frame => Range::Fixed(legend.bounds.0, legend.bounds.1),
+      Range::Fixed(..) => Range::Frame,
+    };
+  }
+  if pressed(keys.legend) {
+    legend.visible = !legend.visible;
+  }
+}
+
+fn refresh(
+  coloring: Res<Coloring>,
+  legend: Res<Legend>,
+  mut roots: Query<&mut Visibility, With<Root>>,
+  mut labels: Query<(&mut Text, &Field)>,
+  mut swatches: Query<(&mut BackgroundColor, &Swatch)>,
+) {
+  if !coloring.is_changed() && !legend.is_changed() {
+    return;
+  }
+
+  for mut visibility in &mut roots {
+    *visibility =
+      if legend.visible { Visibility::Inherited } else { Visibility::Hidden };
+  }
+
+  let (min, max) = legend.bounds;
+  let fixed = matches!(coloring.range, Range::Fixed(..));
+  for (mut text, field) in &mut labels {
+    text.0 = match field {
+      Field::Title => format!(
+        "{} ({}{})",
+        coloring.mode.label(),
+        coloring.colormap.label(),
+        if fixed { ", fixed" } else { "" },
+      ),
+      Field::Min | Field::Max if coloring.mode == ColorMode::Fluid => {
+        let value = if matches!(field, Field::Min) { min } else { max };
+        format!("{value:.0}")
+      }
+      Field::Min => format!("{min:.3}"),
+      Field::Max => format!("{max:.3}"),
+    };
+  }
+
+  for (mut color, Swatch(i)) in &mut swatches {
+    let t = (*i as Real + 0.5) / SWATCHES as Real;
+    color.0 = coloring.colormap.sample(t);
+  }
+}
@@ -1,17 +1,57 @@
+mod bodies;
 mod flow;
+mod headless;
+mod legend;
+mod particles;
+mod playback;
+mod reload;
+mod surfaces;
 mod tick;
+mod timeline;
 
 use {
   crate::{
-    harness::{Fluids, FluidsSnapshot, Plugin},
+    color::Coloring,
+    export::Exporter,
+    harness::{Capture, Fluids, FluidsSnapshot},
     prelude::*,
+    probe::Probes,
+    scene::Scene,
     snapshot::{PhysicsSnapshot, Snapshot},
+    surface::Reconstruction,
   },
   bevy::{app::AppLabel, ecs::schedule::ScheduleLabel},
   harness::Harness,
-  std::time::Duration,
+  std::{path::PathBuf, time::Duration},
 };
 
+pub use {
+  bodies::{BodyView, ColliderView, Selected},
+  flow::{Motion, Profile, Region, ShapeFlow, ShapeSink},
+  headless::{Headless, Progress},
+  legend::{ColoringKeys, Legend},
+  particles::Particles,
+  playback::{Playback, PlaybackKeys, Seek},
+  reload::SceneWatcher,
+  surfaces::Surfaces,
+  tick::{Inflow, Outflow, Pulse, Rate, Schedule},
+  timeline::{Config as TimelineConfig, Header, Timeline, read_header},
+};
+
+/// A simulation ready to be stepped, usually built from a
+/// [`Scene`](crate::scene::Scene).
+pub struct Stand {
+  pub harness: Harness,
+  pub inflows: Vec<Inflow>,
+  pub outflows: Vec<Outflow>,
+  /// Probes recording into their series while the harness steps.
+  pub probes: Probes,
+  /// How the fluid surfaces are reconstructed when shown.
+  pub surface: Reconstruction,
+  /// Stops stepping once the harness reaches this timestep.
+  pub max_steps: Option<usize>,
+}
+
 #[derive(ScheduleLabel, Debug, Hash, PartialEq, Eq, Clone, Default)]
 pub struct Step;
 
@@ -22,24 +62,80 @@ struct FluidApp;
 struct FluidState {
   timer: Timer,
   pause: bool,
+  /// Whether each step is captured into a [`FrameCell`].
+  record: bool,
+  /// Only every `every`-th step is captured.
+  every: usize,
+  limit: Option<usize>,
 }
 
 impl Default for FluidState {
   fn default() -> Self {
-    Self { timer: Timer::from_seconds(1.0, TimerMode::Once), pause: false }
+    Self {
+      timer: Timer::from_seconds(1.0, TimerMode::Once),
+      pause: false,
+      record: true,
+      every: 1,
+      limit: None,
+    }
   }
 }
 
-pub fn plugin(app: &mut App, harness: Harness, fluids: Fluids) {
+/// Builds the sub-app stepping the harness and its plugins on the [`Step`]
+/// schedule, without any windowing or rendering.
+fn sub_app(
+  Stand { harness, inflows, outflows, probes, max_steps, .. }: Stand,
+) -> SubApp {
   let mut sub_app = SubApp::new();
   sub_app.update_schedule = Some(Step.intern());
   sub_app.init_schedule(Main.intern());
 
+  sub_app.world_mut().insert_non_send_resource(harness);
+  sub_app.world_mut().spawn_batch(inflows);
+  sub_app.world_mut().spawn_batch(outflows);
+  sub_app
+    .init_resource::<Time<Sim>>()
+    .insert_resource(probes)
+    .insert_resource(FluidState { limit: max_steps, ..default() })
+    .add_systems(
+      Step,
+      (step, tick::update, tick::drain).chain().run_if(
+        move |state: Res<FluidState>, harness: NonSend<Harness>| {
+          !state.pause
+            && state.limit.is_none_or(|limit| harness.state.timestep_id < limit)
+        },
+      ),
+    );
+  sub_app
+}
+
+pub fn plugin(app: &mut App, stand: Stand) {
+  // Both worlds share the series, the main one to read them.
+  app.insert_resource(stand.probes.clone());
+  app.insert_resource(Surfaces::new(stand.surface));
+  let mut sub_app = sub_app(stand);
   sub_app.set_extract(|main, sub| {
-    if let Some(FrameCell(frame)) = sub.remove_resource::<FrameCell>()
-      && let Some(mut timeline) = main.get_resource_mut::<Timeline>()
+    if let Some(reload::Pending(scene)) =
+      main.remove_resource::<reload::Pending>()
     {
-      timeline.snapshots.push(frame);
+      reload::apply(main, sub, &scene);
+    }
+
+    if let Some(FrameCell(frame)) = sub.remove_resource::<FrameCell>() {
+      if let Some(exporter) = main.get_resource::<Exporter>()
+        && let Err(err) = exporter.export(&frame)
+      {
+        error!("failed to export timestep {}: {err}", frame.0.timestep_id);
+      }
+      if let Some(mut timeline) = main.get_resource_mut::<Timeline>() {
+        timeline.push(frame);
+      }
+    }
+
+    if let Some(timeline) = main.get_resource::<Timeline>()
+      && let Some(mut state) = sub.get_resource_mut::<FluidState>()
+    {
+      state.every = timeline.config().decimation;
     }
 
     if let Some(input) = main.get_resource::<ButtonInput<KeyCode>>()
@@ -48,40 +144,128 @@ pub fn plugin(app: &mut App, harness: Harness, fluids: Fluids) {
     {
       state.pause = !state.pause;
     }
+
+    let rewind = main
+      .get_resource::<ButtonInput<KeyCode>>()
+      .is_some_and(|input| input.just_pressed(KeyCode::KeyR));
+    if rewind
+      && let Some(index) = main
+        .get_resource::<Playback>()
+        .zip(main.get_resource::<Timeline>())
+        .map(|(playback, timeline)| playback.frame(timeline))
+      && let Some(mut timeline) = main.get_resource_mut::<Timeline>()
+    {
+      self::rewind(sub, &mut timeline, index);
+    }
   });
-  sub_app.world_mut().insert_non_send_resource(harness);
-  sub_app.world_mut().insert_non_send_resource(fluids);
-  sub_app
-    .init_resource::<Time<Sim>>()
-    .init_resource::<FluidState>()
+
+  app.insert_sub_app(FluidApp, sub_app);
+  app
+    .init_resource::<Timeline>()
+    .init_resource::<Wireframes>()
+    .add_plugins((
+      playback::plugin,
+      bodies::plugin,
+      legend::plugin,
+      particles::plugin,
+      surfaces::plugin,
+    ))
     .add_systems(
-      Step,
-      (tick::setup, step, tick::update)
-        .chain()
-        .run_if(move |state: Res<FluidState>| !state.pause),
+      Update,
+      (playback::update, legend::update, draw, bodies::sync, surfaces::sync)
+        .chain(),
     );
+}
 
-  app.insert_sub_app(FluidApp, sub_app);
-  app.init_resource::<Timeline>().add_systems(Update, draw);
+/// Rebuilds the stand from the scene at `path` whenever the file changes,
+/// after adjusting the reloaded scene with `prepare`.
+pub fn watch(
+  app: &mut App,
+  path: impl Into<PathBuf>,
+  prepare: impl Fn(&mut Scene) + Send + Sync + 'static,
+) {
+  app
+    .insert_resource(SceneWatcher::new(path, prepare))
+    .add_systems(Update, reload::poll);
+}
+
+/// Plays back a recorded `timeline` without simulating anything.
+pub fn replay(app: &mut App, timeline: Timeline) {
+  app
+    .insert_resource(timeline)
+    .init_resource::<Wireframes>()
+    .add_plugins((
+      playback::plugin,
+      bodies::plugin,
+      legend::plugin,
+      particles::plugin,
+      surfaces::plugin,
+    ))
+    .add_systems(
+      Update,
+      (playback::update, legend::update, draw, bodies::sync, surfaces::sync)
+        .chain(),
+    );
 }
 
 #[derive(Default)]
 struct Sim;
 
+/// Restores the simulation to the last keyframe at or before `index` and
+/// drops it together with every later frame, so the next steps record a new
+/// branch from there. Delta frames do not record the contacts and islands of
+/// the solver, so they cannot be restored.
+fn rewind(sub: &mut World, timeline: &mut Timeline, index: usize) {
+  let Some(index) = timeline.keyframe_before(index) else { return };
+  let Some((physics, fluids)) = timeline.get(index) else { return };
+
+  let mut harness = sub.non_send_resource_mut::<Harness>();
+  harness.restore(physics);
+  if let Some(plugin) = harness.plugin_mut::<Fluids>() {
+    plugin.restore(fluids);
+  }
+
+  let mut time = Time::<Sim>::default();
+  time.advance_by(Duration::from_secs_f32(physics.time));
+  sub.insert_resource(time);
+  if let Some(probes) = sub.get_resource::<Probes>() {
+    probes.truncate(physics.time);
+  }
+  let mut inflows = sub.query::<&mut Inflow>();
+  for mut inflow in inflows.iter_mut(sub) {
+    inflow.rewind(physics.timestep_id);
+  }
+  let mut outflows = sub.query::<&mut Outflow>();
+  for mut outflow in outflows.iter_mut(sub) {
+    outflow.rewind(physics.timestep_id);
+  }
+
+  timeline.truncate(index);
+}
+
 fn step(
   harness: NonSendMut<Harness>,
-  mut fluids: NonSendMut<Fluids>,
   mut time: ResMut<Time<Sim>>,
+  state: Res<FluidState>,
+  mut inflows: Query<&mut Inflow>,
+  mut outflows: Query<&mut Outflow>,
   mut commands: Commands,
 ) {
   let harness = harness.into_inner();
+  if state.record
+    && harness.state.timestep_id % state.every.max(1) == 0
+    && let Some(fluids) = harness.plugin::<Fluids>()
   {
     let physics = PhysicsSnapshot::capture(harness);
-    let fluids = fluids.snapshot();
-    commands.insert_resource(FrameCell((physics, fluids)));
+    commands.insert_resource(FrameCell((physics, fluids.snapshot())));
+    for mut inflow in &mut inflows {
+      inflow.record(harness.state.timestep_id);
+    }
+    for mut outflow in &mut outflows {
+      outflow.record(harness.state.timestep_id);
+    }
   }
   harness.step();
-  fluids.step(&mut harness.physics, &harness.state);
 
   let delta = harness.physics.integration_parameters.dt;
   time.advance_by(Duration::from_secs_f32(delta));
@@ -90,38 +274,43 @@ fn step(
 #[derive(Resource)]
 pub struct FrameCell(Frame);
 
-#[derive(Resource, Default)]
-pub struct Timeline {
-  snapshots: Vec<Frame>,
-  timestamp: usize,
-}
-
-impl Timeline {
-  pub fn step(&mut self) -> Option<&Frame> {
-    if let Some(snapshot) = self.snapshots.get(self.timestamp) {
-      if self.timestamp != self.snapshots.len() - 1 {
-        self.timestamp += 1;
-      }
-      Some(snapshot)
-    } else {
-      None
-    }
-  }
-}
-
 pub type Frame = (PhysicsSnapshot, FluidsSnapshot);
 
+/// Whether the collider wireframes, joints and contacts of the played frame
+/// are drawn, toggled with `G`. Delta frames do not record the narrow phase,
+/// so contacts are only drawn on keyframes.
+#[derive(Resource, Debug, Default)]
+pub struct Wireframes(pub bool);
+
 fn draw(
   mut gizmos: Gizmos,
   mut timeline: ResMut<Timeline>,
-  input: Res<ButtonInput<KeyCode>>,
+  playback: Res<Playback>,
+  mut wireframes: ResMut<Wireframes>,
+  coloring: Res<Coloring>,
+  mut legend: ResMut<Legend>,
+  mut particles: Single<&mut Particles>,
+  input: Option<Res<ButtonInput<KeyCode>>>,
 ) {
-  if input.just_pressed(KeyCode::Space) {
-    timeline.timestamp = 0;
+  if input.is_some_and(|input| input.just_pressed(KeyCode::KeyG)) {
+    wireframes.0 = !wireframes.0;
   }
 
-  if let Some((_physics, fluids)) = timeline.step() {
-    // physics.draw(&mut gizmos);
-    fluids.draw(&mut gizmos);
+  let index = playback.frame(&timeline);
+  let keyframe = timeline.is_keyframe(index);
+  if let Some((physics, fluids)) = timeline.get(index) {
+    if wireframes.0 {
+      physics.draw(&mut gizmos);
+      if keyframe {
+        physics.draw_contacts(&mut gizmos);
+      }
+    }
+    // Mutating the particles would extract them again.
+    let bounds = match particles.shown(physics.timestep_id, &coloring) {
+      Some(bounds) => bounds,
+      None => particles.show(physics.timestep_id, fluids, &coloring),
+    };
+    let visible = legend.visible;
+    legend.set_if_neq(Legend { visible, bounds });
   }
 }
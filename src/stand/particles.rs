@@ -0,0 +1,411 @@
+use {
+  crate::{color::Coloring, harness::FluidsSnapshot, prelude::*},
+  bevy::{
+    asset::embedded_asset,
+    core_pipeline::core_3d::Transparent3d,
+    ecs::{
+      query::QueryItem,
+      system::{SystemParamItem, lifetimeless::*},
+    },
+    pbr::{
+      MeshPipeline, MeshPipelineKey, RenderMeshInstances, SetMeshBindGroup,
+      SetMeshViewBindGroup,
+    },
+    render::{
+      Render, RenderApp, RenderSet,
+      extract_component::{ExtractComponent, ExtractComponentPlugin},
+      mesh::{
+        MeshVertexBufferLayoutRef, RenderMesh, RenderMeshBufferInfo,
+        allocator::MeshAllocator,
+      },
+      render_asset::RenderAssets,
+      render_phase::{
+        AddRenderCommand, DrawFunctions, PhaseItem, PhaseItemExtraIndex,
+        RenderCommand, RenderCommandResult, SetItemPipeline, TrackedRenderPass,
+        ViewSortedRenderPhases,
+      },
+      render_resource::*,
+      renderer::{RenderDevice, RenderQueue},
+      sync_world::MainEntity,
+      view::{ExtractedView, NoFrustumCulling, NoIndirectDrawing},
+    },
+  },
+  bytemuck::{Pod, Zeroable},
+  rapier::math::Point,
+};
+
+const SHADER: &str = "embedded://flux/stand/particles.wgsl";
+
+/// Every particle of the drawn frame, rendered as instances of a single
+/// sphere mesh in one draw call. Only extracted and uploaded when changed, so
+/// callers should check [`Particles::shown`] before mutably borrowing it.
+#[derive(Component, Default, Clone)]
+pub struct Particles {
+  instances: Vec<Instance>,
+  /// The timestep and the coloring the instances were built from.
+  shown: Option<(usize, Coloring)>,
+  bounds: (Real, Real),
+}
+
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+struct Instance {
+  position: [f32; 3],
+  scale: f32,
+  color: [f32; 4],
+}
+
+impl Instance {
+  fn new(position: &Point<Real>, scale: f32, color: Color) -> Self {
+    Self {
+      position: [position.x, position.y, position.z],
+      scale,
+      color: LinearRgba::from(color).to_f32_array(),
+    }
+  }
+}
+
+impl Particles {
+  /// The values mapped onto the ends of the colormap, if the instances
+  /// already show `timestep_id` with `coloring`.
+  pub fn shown(
+    &self,
+    timestep_id: usize,
+    coloring: &Coloring,
+  ) -> Option<(Real, Real)> {
+    (self.shown == Some((timestep_id, *coloring))).then_some(self.bounds)
+  }
+
+  /// Shows the particles of `fluids` captured at `timestep_id`, returning
+  /// the values mapped onto the ends of the colormap.
+  pub fn show(
+    &mut self,
+    timestep_id: usize,
+    fluids: &FluidsSnapshot,
+    coloring: &Coloring,
+  ) -> (Real, Real) {
+    let values = coloring.values(fluids);
+    let bounds = coloring.bounds(&values);
+    let radius = fluids.particle_radius;
+
+    self.instances.clear();
+    for ((_, fluid), values) in fluids.fluids.iter().zip(&values) {
+      self.instances.extend(fluid.positions.iter().zip(values).map(
+        |(point, &value)| {
+          Instance::new(point, radius, coloring.color(value, bounds))
+        },
+      ));
+    }
+    // Boundaries are dense, a fraction of them is enough to see the walls.
+    let boundary = Color::srgb(1.0, 0.2, 0.65);
+    for (_, fluid) in &fluids.boundaries {
+      self.instances.extend(
+        fluid
+          .positions
+          .iter()
+          .step_by(3 * 3 * 3)
+          .map(|point| Instance::new(point, radius, boundary)),
+      );
+    }
+
+    self.shown = Some((timestep_id, *coloring));
+    self.bounds = bounds;
+    bounds
+  }
+
+  pub fn len(&self) -> usize {
+    self.instances.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.instances.is_empty()
+  }
+}
+
+impl ExtractComponent for Particles {
+  type QueryData = &'static Particles;
+  // The render world keeps the last extracted instances.
+  type QueryFilter = Changed<Particles>;
+  type Out = Self;
+
+  fn extract_component(item: QueryItem<'_, Self::QueryData>) -> Option<Self> {
+    Some(item.clone())
+  }
+}
+
+pub fn plugin(app: &mut App) {
+  app.add_plugins(Renderer);
+}
+
+struct Renderer;
+
+impl Plugin for Renderer {
+  fn build(&self, app: &mut App) {
+    embedded_asset!(app, "particles.wgsl");
+    app
+      .add_plugins(ExtractComponentPlugin::<Particles>::default())
+      .add_systems(Startup, spawn)
+      .add_systems(Update, direct_draws);
+
+    let Some(render_app) = app.get_sub_app_mut(RenderApp) else { return };
+    render_app
+      .add_render_command::<Transparent3d, DrawParticles>()
+      .init_resource::<SpecializedMeshPipelines<ParticlePipeline>>()
+      .add_systems(
+        Render,
+        (
+          queue.in_set(RenderSet::QueueMeshes),
+          prepare.in_set(RenderSet::PrepareResources),
+        ),
+      );
+  }
+
+  fn finish(&self, app: &mut App) {
+    if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
+      render_app.init_resource::<ParticlePipeline>();
+    }
+  }
+}
+
+fn spawn(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+  commands.spawn((
+    Particles::default(),
+    Mesh3d(meshes.add(Sphere::new(1.0).mesh().uv(8, 6))),
+    Transform::IDENTITY,
+    Visibility::default(),
+    // The instances are spread far from the mesh itself.
+    NoFrustumCulling,
+  ));
+}
+
+/// The instances are drawn with direct draw calls, which cameras have to
+/// opt into.
+fn direct_draws(
+  mut commands: Commands,
+  cameras: Query<Entity, (With<Camera3d>, Without<NoIndirectDrawing>)>,
+) {
+  for camera in &cameras {
+    commands.entity(camera).insert(NoIndirectDrawing);
+  }
+}
+
+fn queue(
+  draw_functions: Res<DrawFunctions<Transparent3d>>,
+  pipeline: Res<ParticlePipeline>,
+  mut pipelines: ResMut<SpecializedMeshPipelines<ParticlePipeline>>,
+  pipeline_cache: Res<PipelineCache>,
+  meshes: Res<RenderAssets<RenderMesh>>,
+  mesh_instances: Res<RenderMeshInstances>,
+  particles: Query<(Entity, &MainEntity), With<Particles>>,
+  mut phases: ResMut<ViewSortedRenderPhases<Transparent3d>>,
+  views: Query<(&ExtractedView, &Msaa)>,
+) {
+  let draw = draw_functions.read().id::<DrawParticles>();
+
+  for (view, msaa) in &views {
+    let Some(phase) = phases.get_mut(&view.retained_view_entity) else {
+      continue;
+    };
+
+    let view_key = MeshPipelineKey::from_msaa_samples(msaa.samples())
+      | MeshPipelineKey::from_hdr(view.hdr);
+    let rangefinder = view.rangefinder3d();
+    for (entity, main_entity) in &particles {
+      let Some(mesh_instance) =
+        mesh_instances.render_mesh_queue_data(*main_entity)
+      else {
+        continue;
+      };
+      let Some(mesh) = meshes.get(mesh_instance.mesh_asset_id) else {
+        continue;
+      };
+      let key = view_key
+        | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology());
+      let pipeline = match pipelines.specialize(
+        &pipeline_cache,
+        &pipeline,
+        key,
+        &mesh.layout,
+      ) {
+        Ok(pipeline) => pipeline,
+        Err(err) => {
+          error!("failed to specialize the particles pipeline: {err}");
+          continue;
+        }
+      };
+      phase.add(Transparent3d {
+        entity: (entity, *main_entity),
+        pipeline,
+        draw_function: draw,
+        distance: rangefinder.distance_translation(&mesh_instance.translation),
+        batch_range: 0..1,
+        extra_index: PhaseItemExtraIndex::None,
+        indexed: true,
+      });
+    }
+  }
+}
+
+/// Instances uploaded for the GPU, the buffer only growing.
+#[derive(Component)]
+struct InstanceBuffer {
+  buffer: Buffer,
+  length: usize,
+  capacity: usize,
+}
+
+fn prepare(
+  mut commands: Commands,
+  mut particles: Query<
+    (Entity, &Particles, Option<&mut InstanceBuffer>),
+    Changed<Particles>,
+  >,
+  device: Res<RenderDevice>,
+  queue: Res<RenderQueue>,
+) {
+  for (entity, particles, instances) in &mut particles {
+    if particles.is_empty() {
+      commands.entity(entity).remove::<InstanceBuffer>();
+      continue;
+    }
+    let contents = bytemuck::cast_slice(&particles.instances);
+    if let Some(mut instances) = instances
+      && instances.capacity >= particles.len()
+    {
+      queue.write_buffer(&instances.buffer, 0, contents);
+      instances.length = particles.len();
+      continue;
+    }
+
+    let capacity = particles.len().next_power_of_two();
+    let buffer = device.create_buffer(&BufferDescriptor {
+      label: Some("particle instances"),
+      size: (capacity * size_of::<Instance>()) as u64,
+      usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+      mapped_at_creation: false,
+    });
+    queue.write_buffer(&buffer, 0, contents);
+    commands.entity(entity).insert(InstanceBuffer {
+      buffer,
+      length: particles.len(),
+      capacity,
+    });
+  }
+}
+
+#[derive(Resource)]
+struct ParticlePipeline {
+  shader: Handle<Shader>,
+  mesh_pipeline: MeshPipeline,
+}
+
+impl FromWorld for ParticlePipeline {
+  fn from_world(world: &mut World) -> Self {
+    Self {
+      shader: world.load_asset(SHADER),
+      mesh_pipeline: world.resource::<MeshPipeline>().clone(),
+    }
+  }
+}
+
+impl SpecializedMeshPipeline for ParticlePipeline {
+  type Key = MeshPipelineKey;
+
+  fn specialize(
+    &self,
+    key: Self::Key,
+    layout: &MeshVertexBufferLayoutRef,
+  ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
+    let mut descriptor = self.mesh_pipeline.specialize(key, layout)?;
+
+    descriptor.vertex.shader = self.shader.clone();
+    // Locations 0 to 2 hold the position, normal and uv of the mesh.
+    descriptor.vertex.buffers.push(VertexBufferLayout {
+      array_stride: size_of::<Instance>() as u64,
+      step_mode: VertexStepMode::Instance,
+      attributes: vec![
+        VertexAttribute {
+          format: VertexFormat::Float32x4,
+          offset: 0,
+          shader_location: 3,
+        },
+        VertexAttribute {
+          format: VertexFormat::Float32x4,
+          offset: VertexFormat::Float32x4.size(),
+          shader_location: 4,
+        },
+      ],
+    });
+    if let Some(fragment) = &mut descriptor.fragment {
+      fragment.shader = self.shader.clone();
+    }
+    Ok(descriptor)
+  }
+}
+
+type DrawParticles = (
+  SetItemPipeline,
+  SetMeshViewBindGroup<0>,
+  SetMeshBindGroup<1>,
+  DrawInstanced,
+);
+
+struct DrawInstanced;
+
+impl<P: PhaseItem> RenderCommand<P> for DrawInstanced {
+  type Param = (
+    SRes<RenderAssets<RenderMesh>>,
+    SRes<RenderMeshInstances>,
+    SRes<MeshAllocator>,
+  );
+  type ViewQuery = ();
+  type ItemQuery = Read<InstanceBuffer>;
+
+  fn render<'w>(
+    item: &P,
+    _view: (),
+    instances: Option<&'w InstanceBuffer>,
+    (meshes, mesh_instances, allocator): SystemParamItem<'w, '_, Self::Param>,
+    pass: &mut TrackedRenderPass<'w>,
+  ) -> RenderCommandResult {
+    let allocator = allocator.into_inner();
+
+    let Some(instances) = instances else {
+      return RenderCommandResult::Skip;
+    };
+    let Some(mesh_instance) =
+      mesh_instances.render_mesh_queue_data(item.main_entity())
+    else {
+      return RenderCommandResult::Skip;
+    };
+    let id = mesh_instance.mesh_asset_id;
+    let (Some(mesh), Some(vertices)) =
+      (meshes.into_inner().get(id), allocator.mesh_vertex_slice(&id))
+    else {
+      return RenderCommandResult::Skip;
+    };
+
+    pass.set_vertex_buffer(0, vertices.buffer.slice(..));
+    let bytes = (instances.length * size_of::<Instance>()) as u64;
+    pass.set_vertex_buffer(1, instances.buffer.slice(..bytes));
+
+    let count = instances.length as u32;
+    match &mesh.buffer_info {
+      RenderMeshBufferInfo::Indexed { index_format, count: indices } => {
+        let Some(slice) = allocator.mesh_index_slice(&id) else {
+          return RenderCommandResult::Skip;
+        };
+        pass.set_index_buffer(slice.buffer.slice(..), 0, *index_format);
+        pass.draw_indexed(
+          slice.range.start..slice.range.start + indices,
+          vertices.range.start as i32,
+          0..count,
+        );
+      }
+      RenderMeshBufferInfo::NonIndexed => {
+        pass.draw(vertices.range, 0..count);
+      }
+    }
+    RenderCommandResult::Success
+  }
+}
@@ -0,0 +1,35 @@
+#import bevy_pbr::view_transformations::position_world_to_clip
+
+struct Vertex {
+  @location(0) position: vec3<f32>,
+  @location(1) normal: vec3<f32>,
+
+  @location(3) i_position_scale: vec4<f32>,
+  @location(4) i_color: vec4<f32>,
+};
+
+struct VertexOutput {
+  @builtin(position) clip_position: vec4<f32>,
+  @location(0) normal: vec3<f32>,
+  @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+  let position =
+    vertex.position * vertex.i_position_scale.w + vertex.i_position_scale.xyz;
+
+  var out: VertexOutput;
+  out.clip_position = position_world_to_clip(position);
+  out.normal = vertex.normal;
+  out.color = vertex.i_color;
+  return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+  // A fixed headlight-like shading, enough to tell the spheres apart.
+  let light = normalize(vec3<f32>(0.4, 1.0, 0.6));
+  let shade = 0.55 + 0.45 * max(dot(normalize(in.normal), light), 0.0);
+  return vec4<f32>(in.color.rgb * shade, in.color.a);
+}
@@ -0,0 +1,265 @@
+use {super::Timeline, crate::prelude::*, std::ops::RangeInclusive};
+
+const MAX_SPEED: f64 = 64.0;
+/// Simulated seconds skipped by [`PlaybackKeys::skip_forward`] and
+/// [`PlaybackKeys::skip_backward`].
+const SKIP: Real = 1.0;
+
+/// Key bindings of the [`Playback`] controls.
+#[derive(Resource, Debug, Clone)]
+pub struct PlaybackKeys {
+  pub toggle: KeyCode,
+  pub restart: KeyCode,
+  pub forward: KeyCode,
+  pub backward: KeyCode,
+  pub skip_forward: KeyCode,
+  pub skip_backward: KeyCode,
+  pub faster: KeyCode,
+  pub slower: KeyCode,
+  pub looping: KeyCode,
+  pub range_start: KeyCode,
+  pub range_end: KeyCode,
+  pub clear_range: KeyCode,
+  pub live: KeyCode,
+}
+
+impl Default for PlaybackKeys {
+  fn default() -> Self {
+    Self {
+      toggle: KeyCode::Space,
+      restart: KeyCode::Home,
+      forward: KeyCode::ArrowRight,
+      backward: KeyCode::ArrowLeft,
+      skip_forward: KeyCode::PageUp,
+      skip_backward: KeyCode::PageDown,
+      faster: KeyCode::ArrowUp,
+      slower: KeyCode::ArrowDown,
+      looping: KeyCode::KeyL,
+      range_start: KeyCode::BracketLeft,
+      range_end: KeyCode::BracketRight,
+      clear_range: KeyCode::Backslash,
+      live: KeyCode::KeyF,
+    }
+  }
+}
+
+/// Where to move the [`Playback`] cursor, clamped to the recorded frames.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Seek {
+  /// A frame counted like the cursor, from the first frame ever recorded.
+  Frame(usize),
+  /// The first recorded frame at or after this timestep.
+  Timestep(usize),
+  /// The first recorded frame at or after this simulated time, in seconds.
+  Time(Real),
+}
+
+/// Which recorded frame of the [`Timeline`] is drawn, independently of
+/// whether the simulation itself is paused.
+#[derive(Resource, Debug, Clone)]
+pub struct Playback {
+  pub playing: bool,
+  /// Recorded frames advanced per drawn frame.
+  pub speed: f64,
+  pub looping: bool,
+  /// Frames played when looping, the whole timeline when `None`. Counted
+  /// like the cursor from the first frame ever recorded.
+  pub range: Option<RangeInclusive<usize>>,
+  /// Always draws the latest recorded frame.
+  pub follow_live: bool,
+  /// Position counted from the first frame ever recorded, so that evicting
+  /// old frames does not move it.
+  cursor: f64,
+  seek: Option<Seek>,
+}
+
+impl Default for Playback {
+  fn default() -> Self {
+    Self {
+      playing: true,
+      speed: 1.0,
+      looping: false,
+      range: None,
+      follow_live: false,
+      cursor: 0.0,
+      seek: None,
+    }
+  }
+}
+
+impl Playback {
+  pub fn seek(&mut self, seek: Seek) {
+    self.follow_live = false;
+    self.seek = Some(seek);
+  }
+
+  /// Moves back to the first frame of a new recording.
+  pub(super) fn reset(&mut self) {
+    self.cursor = 0.0;
+    self.range = None;
+    self.seek = None;
+  }
+
+  /// Index of the drawn frame in `timeline`.
+  pub fn frame(&self, timeline: &Timeline) -> usize {
+    let last = timeline.len().saturating_sub(1);
+    if self.follow_live {
+      last
+    } else {
+      (self.cursor as usize).saturating_sub(timeline.evicted()).min(last)
+    }
+  }
+
+  fn bounds(&self, timeline: &Timeline) -> (usize, usize) {
+    let last = timeline.len().saturating_sub(1);
+    let index =
+      |frame: usize| frame.saturating_sub(timeline.evicted()).min(last);
+    match &self.range {
+      Some(range) => (index(*range.start()), index(*range.end())),
+      None => (0, last),
+    }
+  }
+
+  fn jump(&mut self, timeline: &Timeline, seek: Seek) {
+    let evicted = timeline.evicted();
+    let frame = match seek {
+      Seek::Frame(frame) => frame,
+      Seek::Timestep(timestep_id) => {
+        timeline.find_timestep(timestep_id) + evicted
+      }
+      Seek::Time(time) => timeline.find_time(time) + evicted,
+    };
+    let last = evicted + timeline.len().saturating_sub(1);
+    self.cursor = frame.clamp(evicted, last) as f64;
+  }
+
+  fn advance(&mut self, timeline: &Timeline) {
+    let (start, end) = self.bounds(timeline);
+    let offset = timeline.evicted() as f64;
+
+    self.cursor = self.cursor.max(start as f64 + offset) + self.speed;
+    if self.cursor >= end as f64 + offset + 1.0 {
+      self.cursor =
+        if self.looping { start as f64 + offset } else { end as f64 + offset };
+    }
+  }
+}
+
+pub fn plugin(app: &mut App) {
+  app.init_resource::<Playback>().init_resource::<PlaybackKeys>();
+}
+
+pub fn update(
+  mut playback: ResMut<Playback>,
+  timeline: Res<Timeline>,
+  keys: Res<PlaybackKeys>,
+  input: Res<ButtonInput<KeyCode>>,
+) {
+  if timeline.is_empty() {
+    return;
+  }
+
+  let index = playback.frame(&timeline);
+  let evicted = timeline.evicted();
+  // Counted like the cursor, from the first frame ever recorded.
+  let current = index + evicted;
+  let pressed = |key| input.just_pressed(key);
+
+  if pressed(keys.toggle) {
+    playback.playing = !playback.playing;
+  }
+  if pressed(keys.restart) {
+    let (start, _) = playback.bounds(&timeline);
+    playback.seek(Seek::Frame(start + evicted));
+  }
+  if pressed(keys.forward) {
+    playback.playing = false;
+    playback.seek(Seek::Frame(current + 1));
+  }
+  if pressed(keys.backward) {
+    playback.playing = false;
+    playback.seek(Seek::Frame(current.saturating_sub(1)));
+  }
+  if let Some(time) = timeline.time(index) {
+    if pressed(keys.skip_forward) {
+      playback.seek(Seek::Time(time + SKIP));
+    }
+    if pressed(keys.skip_backward) {
+      playback.seek(Seek::Time(time - SKIP));
+    }
+  }
+  if pressed(keys.faster) {
+    playback.speed = (playback.speed * 2.0).min(MAX_SPEED);
+  }
+  if pressed(keys.slower) {
+    playback.speed = (playback.speed / 2.0).max(MAX_SPEED.recip());
+  }
+  if pressed(keys.looping) {
+    playback.looping = !playback.looping;
+  }
+  if pressed(keys.range_start) {
+    let end = playback.range.as_ref().map_or(usize::MAX, |range| *range.end());
+    playback.range = Some(current..=end.max(current));
+  }
+  if pressed(keys.range_end) {
+    let start = playback.range.as_ref().map_or(0, |range| *range.start());
+    playback.range = Some(start.min(current)..=current);
+  }
+  // Evicted frames cannot be played anymore.
+  if let Some(range) = &mut playback.range
+    && *range.start() < evicted
+  {
+    *range = evicted..=(*range.end()).max(evicted);
+  }
+  if pressed(keys.clear_range) {
+    playback.range = None;
+  }
+  if pressed(keys.live) {
+    playback.follow_live = !playback.follow_live;
+  }
+
+  if let Some(seek) = playback.seek.take() {
+    playback.jump(&timeline, seek);
+  } else if playback.playing && !playback.follow_live {
+    playback.advance(&timeline);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    crate::stand::{TimelineConfig, timeline::tests::frames},
+  };
+
+  #[test]
+  fn seeks_count_frames_like_the_cursor() {
+    let frames = frames(16);
+    let config = TimelineConfig { keyframe: 2, ..default() };
+    let mut first = Timeline::with_config(config);
+    for frame in &frames[..6] {
+      first.push(frame.clone());
+    }
+    let mut timeline =
+      Timeline::with_config(TimelineConfig { budget: first.bytes(), ..config });
+    for frame in frames {
+      timeline.push(frame);
+    }
+    let evicted = timeline.evicted();
+    assert!(evicted > 0 && timeline.len() > 2);
+
+    let mut playback = Playback::default();
+    playback.jump(&timeline, Seek::Frame(evicted + 1));
+    assert_eq!(playback.frame(&timeline), 1);
+    // Frames are recorded every timestep, at 60 steps per second.
+    playback.jump(&timeline, Seek::Timestep(evicted + 2));
+    assert_eq!(playback.frame(&timeline), 2);
+    playback.jump(&timeline, Seek::Time((evicted + 1) as Real / 60.0));
+    assert_eq!(playback.frame(&timeline), 1);
+    // Evicted frames cannot be played anymore.
+    playback.jump(&timeline, Seek::Frame(0));
+    assert_eq!(playback.frame(&timeline), 0);
+    playback.jump(&timeline, Seek::Time(Real::MAX));
+    assert_eq!(playback.frame(&timeline), timeline.len() - 1);
+  }
+}
@@ -0,0 +1,116 @@
+use {
+  super::{
+    FrameCell, Inflow, Outflow, Playback, Sim, Stand, Surfaces, Timeline,
+  },
+  crate::{prelude::*, probe::Probes, scene::Scene},
+  std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+  },
+};
+
+/// Polls a scene file and rebuilds the stand whenever it changes.
+#[derive(Resource)]
+pub struct SceneWatcher {
+  path: PathBuf,
+  modified: Option<SystemTime>,
+  timer: Timer,
+  prepare: Box<dyn Fn(&mut Scene) + Send + Sync>,
+}
+
+/// A changed scene waiting to be built by the sub-app extraction.
+#[derive(Resource)]
+pub(super) struct Pending(pub Scene);
+
+impl SceneWatcher {
+  pub fn new(
+    path: impl Into<PathBuf>,
+    prepare: impl Fn(&mut Scene) + Send + Sync + 'static,
+  ) -> Self {
+    let path = path.into();
+    let modified = modified(&path);
+    Self {
+      path,
+      modified,
+      timer: Timer::from_seconds(0.5, TimerMode::Repeating),
+      prepare: Box::new(prepare),
+    }
+  }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+  fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+pub fn poll(
+  time: Res<Time>,
+  mut watcher: ResMut<SceneWatcher>,
+  mut commands: Commands,
+) {
+  if !watcher.timer.tick(time.delta()).just_finished() {
+    return;
+  }
+
+  let modified = modified(&watcher.path);
+  if modified.is_none() || modified == watcher.modified {
+    return;
+  }
+  watcher.modified = modified;
+
+  match Scene::load(&watcher.path) {
+    Ok(mut scene) => {
+      info!("reloading {}", watcher.path.display());
+      (watcher.prepare)(&mut scene);
+      commands.insert_resource(Pending(scene));
+    }
+    Err(err) => error!("failed to reload {}: {err}", watcher.path.display()),
+  }
+}
+
+/// Replaces the simulated stand with `scene` and restarts the recording.
+pub(super) fn apply(main: &mut World, sub: &mut World, scene: &Scene) {
+  let Stand { harness, inflows, outflows, probes, .. } = match scene.build() {
+    Ok(stand) => stand,
+    Err(err) => {
+      error!("failed to build the reloaded scene: {err}");
+      return;
+    }
+  };
+
+  // Keeps the thread count the running harness was given.
+  #[cfg(feature = "parallel")]
+  let harness = {
+    let mut harness = harness;
+    if let Some(running) = sub.get_non_send_resource::<harness::Harness>() {
+      harness.state.set_num_threads(running.state.num_threads());
+    }
+    harness
+  };
+
+  let stale: Vec<_> = sub
+    .query_filtered::<Entity, Or<(With<Inflow>, With<Outflow>)>>()
+    .iter(sub)
+    .collect();
+  for entity in stale {
+    sub.despawn(entity);
+  }
+  sub.insert_non_send_resource(harness);
+  sub.spawn_batch(inflows);
+  sub.spawn_batch(outflows);
+  sub.insert_resource(Time::<Sim>::default());
+  sub.remove_resource::<FrameCell>();
+  sub.insert_resource(probes.clone());
+  main.insert_resource(probes);
+
+  if let Some(mut timeline) = main.get_resource_mut::<Timeline>() {
+    *timeline = Timeline::with_config(*timeline.config());
+  }
+  if let Some(mut playback) = main.get_resource_mut::<Playback>() {
+    playback.reset();
+  }
+  if let Some(mut surfaces) = main.get_resource_mut::<Surfaces>() {
+    surfaces.reset(scene.surface);
+  }
+  super::bodies::clear(main);
+}
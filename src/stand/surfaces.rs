@@ -0,0 +1,125 @@
+use {
+  super::{Playback, Timeline},
+  crate::{prelude::*, surface::Reconstruction},
+  bevy::tasks::{AsyncComputeTaskPool, Task, block_on, futures_lite::future},
+};
+
+/// The reconstructed surfaces of the fluids in the played frame, toggled
+/// with `M`. Surfaces are reconstructed in the background, one frame at a
+/// time, the meshes of the previous one staying shown meanwhile.
+#[derive(Resource, Debug, Default)]
+pub struct Surfaces {
+  pub visible: bool,
+  pub reconstruction: Reconstruction,
+  /// The timestep and the reconstruction the meshes were built from.
+  shown: Option<(usize, Reconstruction)>,
+  /// The running reconstruction with what it is built from.
+  task: Option<((usize, Reconstruction), Task<Vec<Mesh>>)>,
+  views: Vec<(Entity, Handle<Mesh>)>,
+  material: Option<Handle<StandardMaterial>>,
+}
+
+impl Surfaces {
+  pub fn new(reconstruction: Reconstruction) -> Self {
+    Self { reconstruction, ..default() }
+  }
+
+  /// Reconstructs the surfaces of a new recording with `reconstruction`.
+  pub(super) fn reset(&mut self, reconstruction: Reconstruction) {
+    self.reconstruction = reconstruction;
+    self.shown = None;
+    self.task = None;
+  }
+}
+
+pub fn plugin(app: &mut App) {
+  app.init_resource::<Surfaces>();
+}
+
+pub fn sync(
+  mut commands: Commands,
+  mut timeline: ResMut<Timeline>,
+  playback: Res<Playback>,
+  mut surfaces: ResMut<Surfaces>,
+  input: Option<Res<ButtonInput<KeyCode>>>,
+  mut meshes: ResMut<Assets<Mesh>>,
+  mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+  if input.is_some_and(|input| input.just_pressed(KeyCode::KeyM)) {
+    surfaces.visible = !surfaces.visible;
+    let visibility =
+      if surfaces.visible { Visibility::Inherited } else { Visibility::Hidden };
+    for &(entity, _) in &surfaces.views {
+      commands.entity(entity).insert(visibility);
+    }
+  }
+  if !surfaces.visible {
+    return;
+  }
+
+  let surfaces = &mut *surfaces;
+  if let Some((shown, task)) = &mut surfaces.task {
+    let Some(rendered) = block_on(future::poll_once(task)) else { return };
+    surfaces.shown = Some(*shown);
+    surfaces.task = None;
+
+    let material = surfaces
+      .material
+      .get_or_insert_with(|| {
+        materials.add(StandardMaterial {
+          base_color: Color::srgba(0.2, 0.45, 0.9, 0.7),
+          alpha_mode: AlphaMode::Blend,
+          double_sided: true,
+          cull_mode: None,
+          ..default()
+        })
+      })
+      .clone();
+
+    let count = rendered.len();
+    for (id, mesh) in rendered.into_iter().enumerate() {
+      match surfaces.views.get(id) {
+        Some((_, handle)) => {
+          meshes.insert(handle, mesh);
+        }
+        None => {
+          let handle = meshes.add(mesh);
+          let entity = commands
+            .spawn((
+              Name::new(format!("surface {id}")),
+              Mesh3d(handle.clone()),
+              MeshMaterial3d(material.clone()),
+              Transform::IDENTITY,
+            ))
+            .id();
+          surfaces.views.push((entity, handle));
+        }
+      }
+    }
+
+    // Fluids removed since.
+    for (entity, handle) in surfaces.views.split_off(count) {
+      commands.entity(entity).despawn();
+      meshes.remove(&handle);
+    }
+  }
+
+  let index = playback.frame(&timeline);
+  let Some((physics, fluids)) = timeline.get(index) else { return };
+  let key = (physics.timestep_id, surfaces.reconstruction);
+  if surfaces.shown == Some(key) {
+    return;
+  }
+
+  let radius = fluids.particle_radius;
+  let reconstruction = surfaces.reconstruction;
+  let points: Vec<_> =
+    fluids.fluids.iter().map(|(_, fluid)| fluid.positions.clone()).collect();
+  let task = AsyncComputeTaskPool::get().spawn(async move {
+    points
+      .iter()
+      .map(|points| reconstruction.reconstruct(points, radius).to_render())
+      .collect()
+  });
+  surfaces.task = Some((key, task));
+}
@@ -1,63 +1,265 @@
 use {
-  crate::{harness::Fluids, prelude::*, stand::flow::ShapeFlow},
-  parry::shape::Ball,
-  salva::{
-    math::{Isometry, Vector},
-    object::FluidHandle,
-    parry, solver,
+  crate::{
+    harness::{Fluids, Harness},
+    prelude::*,
+    stand::flow::{Motion, ShapeFlow, ShapeSink},
   },
+  rapier::dynamics::{RigidBodyHandle, RigidBodySet},
+  salva::{math::Isometry, object::FluidHandle},
+  serde::{Deserialize, Serialize},
+  std::collections::HashSet,
 };
 
-pub fn setup(
-  mut fluids: NonSendMut<Fluids>,
-  mut commands: Commands,
-  mut run: Local<bool>,
-) {
-  if *run {
-    return;
-  } else {
-    *run = true;
+/// How many particles an [`Inflow`] emits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Rate {
+  /// Fills every free sample of the shape each step.
+  #[default]
+  Fill,
+  /// Particles per second.
+  Particles(f32),
+  /// Volume per second, each particle carrying a `(2 * radius)^3` cube.
+  Volume(f32),
+}
+
+/// Emits during the first `duty` fraction of every `period` seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Pulse {
+  pub period: f32,
+  pub duty: f32,
+}
+
+/// When an [`Inflow`] is active, in simulated seconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Schedule {
+  pub start: f32,
+  pub stop: Option<f32>,
+  pub pulse: Option<Pulse>,
+  /// Duration of the linear ramp from zero to the full rate after `start`.
+  pub ramp: f32,
+}
+
+impl Schedule {
+  /// Fraction of the rate emitted at `time`.
+  pub fn factor(&self, time: f32) -> f32 {
+    let local = time - self.start;
+    if local < 0.0 || self.stop.is_some_and(|stop| time >= stop) {
+      return 0.0;
+    }
+    if let Some(Pulse { period, duty }) = self.pulse
+      && period > 0.0
+      && local.rem_euclid(period) >= duty * period
+    {
+      return 0.0;
+    }
+    if self.ramp > 0.0 { (local / self.ramp).min(1.0) } else { 1.0 }
   }
+}
 
-  let world = &mut fluids.pipeline.liquid_world;
-  let particle_radius = world.particle_radius();
+#[derive(Component)]
+pub struct Inflow {
+  flow: ShapeFlow,
+  handle: FluidHandle,
+  /// The body the flow moves with and its pose relative to the body.
+  body: Option<(RigidBodyHandle, Isometry<f32>)>,
+  rate: Rate,
+  schedule: Schedule,
+  /// Stops emitting once this amount of particles was emitted.
+  budget: Option<usize>,
+  emitted: usize,
+  /// Fractional particles owed by the previous steps.
+  carry: f32,
+  /// `emitted` and `carry` at every recorded timestep, restored on rewind.
+  history: Vec<(usize, usize, f32)>,
+}
 
-  use solver::{Akinci2013SurfaceTension, ArtificialViscosity, XSPHViscosity};
+impl Inflow {
+  pub fn new(flow: ShapeFlow, handle: FluidHandle) -> Self {
+    Self {
+      flow,
+      handle,
+      body: None,
+      rate: Rate::Fill,
+      schedule: Schedule::default(),
+      budget: None,
+      emitted: 0,
+      carry: 0.0,
+      history: Vec::new(),
+    }
+  }
+
+  /// Moves the flow with `body`, placing it at `offset` in the body frame.
+  /// The flow velocity turns with the body and the velocity of the body at
+  /// each particle is added to it.
+  pub fn attached_to(
+    mut self,
+    body: RigidBodyHandle,
+    offset: Isometry<f32>,
+  ) -> Self {
+    self.body = Some((body, offset));
+    self
+  }
+
+  pub fn with_rate(mut self, rate: Rate) -> Self {
+    self.rate = rate;
+    self
+  }
+
+  pub fn with_schedule(mut self, schedule: Schedule) -> Self {
+    self.schedule = schedule;
+    self
+  }
+
+  pub fn with_budget(mut self, budget: usize) -> Self {
+    self.budget = Some(budget);
+    self
+  }
 
-  let viscosity = XSPHViscosity::new(0.5, 0.5);
-  let mut fluid = helper::cube_fluid(15, 15, 15, particle_radius, 1000.0);
-  fluid.nonpressure_forces.push(Box::new(viscosity));
-  fluid.transform_by(&Isometry::translation(0.0, -5.0, 0.0));
-  fluid.nonpressure_forces.push(Box::new(ArtificialViscosity::new(1.0, 0.0)));
+  /// Particles emitted since the stand started.
+  pub fn emitted(&self) -> usize {
+    self.emitted
+  }
 
-  let _fluid_handle = world.add_fluid(fluid);
+  /// Remembers the emission state at the start of `timestep_id`.
+  pub(super) fn record(&mut self, timestep_id: usize) {
+    self.history.push((timestep_id, self.emitted, self.carry));
+  }
 
-  let surface_tension = Akinci2013SurfaceTension::new(0.1, 1.0);
-  let mut fluid = helper::cube_fluid(0, 0, 0, particle_radius, 1000.0);
-  fluid.transform_by(&Isometry::translation(0.0, 0.08, 0.0));
-  fluid.nonpressure_forces.push(Box::new(surface_tension));
-  let handle = world.add_fluid(fluid);
+  /// Restores the emission state recorded at `timestep_id`, forgetting the
+  /// later ones.
+  pub(super) fn rewind(&mut self, timestep_id: usize) {
+    let index = self.history.partition_point(|&(id, ..)| id < timestep_id);
+    if let Some(&(id, emitted, carry)) = self.history.get(index)
+      && id == timestep_id
+    {
+      self.emitted = emitted;
+      self.carry = carry;
+    }
+    self.history.truncate(index);
+  }
 
-  let flow = ShapeFlow::new(
-    Vector::new(-10.0, 0.0, 0.0),
-    &Ball::new(0.2),
-    particle_radius,
-  )
-  .unwrap()
-  .with_velocity(Vector::new(1.0, 1.0, 0.0) * 5.0);
-  commands.spawn(Inflow { flow, handle });
+  /// The frame the flow is emitted in, `None` once its body was removed.
+  fn motion(&self, bodies: &RigidBodySet) -> Option<Motion> {
+    let Some((handle, offset)) = self.body else {
+      return Some(Motion::default());
+    };
+    let body = bodies.get(handle)?;
+    Some(Motion {
+      pose: body.position() * offset,
+      linvel: *body.linvel(),
+      angvel: *body.angvel(),
+      center: *body.center_of_mass(),
+    })
+  }
+
+  /// Amount of particles to emit for a step of `dt` ending at `time`.
+  fn demand(&mut self, time: f32, dt: f32) -> usize {
+    let factor = self.schedule.factor(time);
+    let capacity = self.flow.capacity();
+
+    let per_second = match self.rate {
+      Rate::Fill => None,
+      Rate::Particles(particles) => Some(particles),
+      Rate::Volume(volume) => Some(volume / (2.0 * self.flow.radius()).powi(3)),
+    };
+    let count = match per_second {
+      // Nozzles may emit several layers within a single step.
+      None if factor >= 1.0 => usize::MAX,
+      None => (capacity as f32 * factor).ceil() as usize,
+      Some(per_second) => {
+        // Blocked samples must not accumulate into a burst.
+        self.carry =
+          (self.carry + per_second * factor * dt).min(capacity as f32);
+        self.carry as usize
+      }
+    };
+
+    match self.budget {
+      Some(budget) => count.min(budget.saturating_sub(self.emitted)),
+      None => count,
+    }
+  }
+}
+
+pub fn update(mut flows: Query<&mut Inflow>, mut harness: NonSendMut<Harness>) {
+  let (time, dt) =
+    (harness.state.time, harness.physics.integration_parameters.dt);
+  let flows: Vec<_> = flows
+    .iter_mut()
+    .filter_map(|inflow| {
+      let motion = inflow.motion(&harness.physics.bodies)?;
+      Some((inflow, motion))
+    })
+    .collect();
+
+  let Some(fluids) = harness.plugin_mut::<Fluids>() else { return };
+  let world = &mut fluids.pipeline.liquid_world;
+
+  for (mut inflow, motion) in flows {
+    let inflow = &mut *inflow;
+    let demand = inflow.demand(time, dt);
+    if demand == 0 {
+      continue;
+    }
+
+    let emitted =
+      inflow.flow.emit_moving(world, inflow.handle, demand, dt, &motion);
+    inflow.emitted += emitted;
+    if inflow.rate != Rate::Fill {
+      inflow.carry -= emitted as f32;
+    }
+  }
 }
 
 #[derive(Component)]
-pub struct Inflow {
-  flow: ShapeFlow,
-  handle: FluidHandle,
+pub struct Outflow {
+  sink: ShapeSink,
+  /// Particles removed during the last step.
+  pub removed: usize,
+  /// Particles removed since the stand started.
+  pub total: usize,
+  /// `total` at every recorded timestep, restored on rewind.
+  history: Vec<(usize, usize)>,
+}
+
+impl Outflow {
+  pub fn new(sink: ShapeSink) -> Self {
+    Self { sink, removed: 0, total: 0, history: Vec::new() }
+  }
+
+  pub fn sink(&self) -> &ShapeSink {
+    &self.sink
+  }
+
+  /// Remembers the removed particles at the start of `timestep_id`.
+  pub(super) fn record(&mut self, timestep_id: usize) {
+    self.history.push((timestep_id, self.total));
+  }
+
+  /// Restores the removed particles recorded at `timestep_id`, forgetting
+  /// the later ones.
+  pub(super) fn rewind(&mut self, timestep_id: usize) {
+    let index = self.history.partition_point(|&(id, _)| id < timestep_id);
+    if let Some(&(id, total)) = self.history.get(index)
+      && id == timestep_id
+    {
+      self.removed = 0;
+      self.total = total;
+    }
+    self.history.truncate(index);
+  }
 }
 
-pub fn update(flows: Query<&Inflow>, mut fluids: NonSendMut<Fluids>) {
+pub fn drain(mut sinks: Query<&mut Outflow>, mut harness: NonSendMut<Harness>) {
+  let Some(fluids) = harness.plugin_mut::<Fluids>() else { return };
   let world = &mut fluids.pipeline.liquid_world;
 
-  for &Inflow { ref flow, handle } in flows.iter() {
-    flow.emit(world, handle);
+  // Overlapping sinks count each particle once.
+  let mut marked = HashSet::new();
+  for mut outflow in sinks.iter_mut() {
+    let removed = outflow.sink.drain(world, &mut marked);
+    outflow.removed = removed;
+    outflow.total += removed;
   }
 }
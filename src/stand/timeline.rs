@@ -0,0 +1,758 @@
+use {
+  super::Frame,
+  crate::{
+    harness::{Fields, FluidForce},
+    prelude::*,
+  },
+  rapier::{
+    dynamics::{RigidBody, RigidBodyHandle},
+    geometry::{Collider, ContactPair},
+    math::{Isometry, Point, Vector},
+  },
+  salva::object::{BoundaryHandle, FluidHandle},
+  serde::{Deserialize, Serialize},
+  std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+  },
+};
+
+const MAGIC: &[u8; 4] = b"FLUX";
+const VERSION: u32 = 9;
+
+/// How a [`Timeline`] records frames.
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+  /// Upper bound of the memory held by the recorded frames in bytes, the
+  /// oldest frames are dropped first.
+  pub budget: usize,
+  /// A full frame is kept every `keyframe` recorded frames, the frames in
+  /// between only store moving bodies, moved boundaries and quantized
+  /// particles and fields.
+  pub keyframe: usize,
+  /// Only every `decimation`-th step is recorded.
+  pub decimation: usize,
+  /// Quantization step of particle positions, relative to the particle
+  /// radius.
+  pub precision: Real,
+}
+
+impl Default for Config {
+  fn default() -> Self {
+    Self { budget: 1 << 30, keyframe: 60, decimation: 1, precision: 1e-3 }
+  }
+}
+
+/// Recorded frames of a simulation, played back by the stand.
+#[derive(Resource, Default)]
+pub struct Timeline {
+  config: Config,
+  records: Vec<Record>,
+  sizes: Vec<usize>,
+  bytes: usize,
+  /// Decoded particle positions of the last record, deltas are encoded
+  /// against them so that quantization errors do not accumulate.
+  reference: Option<Reference>,
+  since_key: usize,
+  /// The last decoded frame and its index.
+  cache: Option<(usize, Frame)>,
+  /// Amount of frames dropped to fit into the budget.
+  evicted: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Record {
+  Key(Frame),
+  Delta(Delta),
+}
+
+/// Particles of the last record as a decoder sees them.
+struct Reference {
+  fluids: Vec<(FluidHandle, Vec<Point<Real>>)>,
+  boundaries: Vec<(BoundaryHandle, Vec<Point<Real>>)>,
+}
+
+/// Changes since the previous record.
+#[derive(Serialize, Deserialize)]
+struct Delta {
+  timestep_id: usize,
+  time: Real,
+  bodies: Vec<BodyState>,
+  /// Boundaries whose particles changed, stored as captured.
+  boundaries: Vec<(BoundaryHandle, Vec<Point<Real>>)>,
+  forces: Vec<(RigidBodyHandle, FluidForce)>,
+  /// Size of a single quantization step of positions.
+  step: Real,
+  fluids: Vec<QuantizedFluid>,
+}
+
+/// A moving body, with the velocities a rewind restores.
+#[derive(Serialize, Deserialize)]
+struct BodyState {
+  handle: RigidBodyHandle,
+  position: Isometry<Real>,
+  linvel: Vector<Real>,
+  angvel: Vector<Real>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct QuantizedFluid {
+  /// Displacement of every particle, in quantization steps.
+  offsets: Vec<[i16; 3]>,
+  velocity_scale: Real,
+  velocities: Vec<[i16; 3]>,
+  fields: QuantizedFields,
+}
+
+/// Captured [`Fields`], each scaled to the largest of its values like the
+/// velocities.
+#[derive(Serialize, Deserialize)]
+struct QuantizedFields {
+  acceleration_scale: Real,
+  accelerations: Vec<[i16; 3]>,
+  density_scale: Real,
+  densities: Vec<i16>,
+  error_scale: Real,
+  density_error: Vec<i16>,
+  pressure_scale: Real,
+  pressures: Vec<i16>,
+  neighbours: Vec<u16>,
+}
+
+/// The fixed-size header of a recorded [`Timeline`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Header {
+  pub particle_radius: Real,
+  pub dt: Real,
+  pub frames: u64,
+}
+
+impl Timeline {
+  pub fn with_config(config: Config) -> Self {
+    Self { config, ..Default::default() }
+  }
+
+  pub fn config(&self) -> &Config {
+    &self.config
+  }
+
+  /// Records `frame`, unless it is skipped by the decimation.
+  pub fn push(&mut self, frame: Frame) {
+    if frame.0.timestep_id % self.config.decimation.max(1) != 0 {
+      return;
+    }
+
+    let delta = if self.since_key + 1 < self.config.keyframe {
+      let step = self.config.precision * frame.1.particle_radius;
+      self.reference.as_ref().and_then(|prev| Delta::encode(prev, &frame, step))
+    } else {
+      None
+    };
+
+    let record = match delta {
+      Some((delta, reference)) => {
+        self.reference = Some(reference);
+        self.since_key += 1;
+        Record::Delta(delta)
+      }
+      None => {
+        self.reference = Some(Reference::new(&frame));
+        self.since_key = 0;
+        Record::Key(frame)
+      }
+    };
+
+    let size = size(&record);
+    self.records.push(record);
+    self.sizes.push(size);
+    self.bytes += size;
+    self.evict();
+  }
+
+  /// Decodes the frame at `index`, reusing the last decoded frame when
+  /// possible.
+  pub fn get(&mut self, index: usize) -> Option<&Frame> {
+    if index >= self.records.len() {
+      return None;
+    }
+    let key = self.keyframe_before(index)?;
+
+    let start = match self.cache {
+      Some((cached, _)) if (key..=index).contains(&cached) => cached + 1,
+      _ => {
+        let Record::Key(frame) = &self.records[key] else { return None };
+        self.cache = Some((key, frame.clone()));
+        key + 1
+      }
+    };
+
+    let (cached, frame) = self.cache.as_mut()?;
+    for record in &self.records[start..=index] {
+      if let Record::Delta(delta) = record {
+        delta.apply(frame);
+      }
+    }
+    *cached = index;
+    Some(frame)
+  }
+
+  /// Whether the frame at `index` was recorded whole. Delta frames only
+  /// update bodies and particles, the rest of a decoded delta frame, like
+  /// its contacts, is the one of the previous keyframe.
+  pub fn is_keyframe(&self, index: usize) -> bool {
+    matches!(self.records.get(index), Some(Record::Key(_)))
+  }
+
+  /// Index of the last keyframe at or before `index`.
+  pub fn keyframe_before(&self, index: usize) -> Option<usize> {
+    let records = self.records.get(..=index)?;
+    records.iter().rposition(|record| matches!(record, Record::Key(_)))
+  }
+
+  /// Index of the first frame at or after `timestep_id`.
+  pub fn find_timestep(&self, timestep_id: usize) -> usize {
+    self.records.partition_point(|record| record.timestep_id() < timestep_id)
+  }
+
+  /// Index of the first frame at or after the simulation `time`.
+  pub fn find_time(&self, time: Real) -> usize {
+    self.records.partition_point(|record| record.time() < time)
+  }
+
+  /// Simulated time of the frame at `index`, without decoding it.
+  pub fn time(&self, index: usize) -> Option<Real> {
+    self.records.get(index).map(Record::time)
+  }
+
+  /// Drops the frame at `index` and every later one.
+  pub fn truncate(&mut self, index: usize) {
+    self.records.truncate(index);
+    self.sizes.truncate(index);
+    self.bytes = self.sizes.iter().sum();
+    self.reference = None;
+    if self.cache.as_ref().is_some_and(|&(cached, _)| cached >= index) {
+      self.cache = None;
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    self.records.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.records.is_empty()
+  }
+
+  /// Approximate memory held by the recorded frames in bytes.
+  pub fn bytes(&self) -> usize {
+    self.bytes
+  }
+
+  /// Amount of frames dropped from the front so far, frame indices are
+  /// shifted by the same amount.
+  pub fn evicted(&self) -> usize {
+    self.evicted
+  }
+
+  /// Drops the oldest frames, up to the next keyframe, until the recorded
+  /// frames fit into the budget.
+  fn evict(&mut self) {
+    while self.bytes > self.config.budget {
+      let Some(next) = self
+        .records
+        .iter()
+        .skip(1)
+        .position(|record| matches!(record, Record::Key(_)))
+        .map(|i| i + 1)
+      else {
+        break;
+      };
+
+      self.records.drain(..next);
+      self.bytes -= self.sizes.drain(..next).sum::<usize>();
+      self.evicted += next;
+      self.cache = match self.cache.take() {
+        Some((cached, frame)) if cached >= next => Some((cached - next, frame)),
+        _ => None,
+      };
+    }
+  }
+
+  pub fn header(&self) -> Header {
+    let (particle_radius, dt) = match self.records.first() {
+      Some(Record::Key((physics, fluids))) => {
+        (fluids.particle_radius, physics.dt)
+      }
+      _ => (0.0, 0.0),
+    };
+    Header { particle_radius, dt, frames: self.records.len() as u64 }
+  }
+
+  pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    self.write(&mut writer)?;
+    writer.flush()
+  }
+
+  pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+    Self::read(BufReader::new(File::open(path)?))
+  }
+
+  /// Writes the header followed by every frame.
+  pub fn write(&self, mut writer: impl Write) -> io::Result<()> {
+    let Header { particle_radius, dt, frames } = self.header();
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    writer.write_all(&particle_radius.to_le_bytes())?;
+    writer.write_all(&dt.to_le_bytes())?;
+    writer.write_all(&frames.to_le_bytes())?;
+
+    for record in &self.records {
+      bincode::serialize_into(&mut writer, record).map_err(io::Error::other)?;
+    }
+    Ok(())
+  }
+
+  pub fn read(mut reader: impl Read) -> io::Result<Self> {
+    let Header { frames, .. } = read_header(&mut reader)?;
+
+    let records: Vec<Record> = (0..frames)
+      .map(|_| bincode::deserialize_from(&mut reader))
+      .collect::<Result<_, _>>()
+      .map_err(io::Error::other)?;
+    let sizes: Vec<_> = records.iter().map(size).collect();
+
+    Ok(Self { bytes: sizes.iter().sum(), records, sizes, ..Default::default() })
+  }
+}
+
+impl Record {
+  fn timestep_id(&self) -> usize {
+    match self {
+      Record::Key((physics, _)) => physics.timestep_id,
+      Record::Delta(delta) => delta.timestep_id,
+    }
+  }
+
+  fn time(&self) -> Real {
+    match self {
+      Record::Key((physics, _)) => physics.time,
+      Record::Delta(delta) => delta.time,
+    }
+  }
+}
+
+impl Delta {
+  /// Encodes `frame` against the `reference` positions, returning the
+  /// positions a decoder will see. Fails if the particles or the boundaries
+  /// changed or moved too far to be quantized.
+  fn encode(
+    reference: &Reference,
+    (physics, fluids): &Frame,
+    step: Real,
+  ) -> Option<(Self, Reference)> {
+    if reference.fluids.len() != fluids.fluids.len()
+      || reference.boundaries.len() != fluids.boundaries.len()
+    {
+      return None;
+    }
+
+    let mut quantized = Vec::with_capacity(fluids.fluids.len());
+    let mut decoded = Vec::with_capacity(fluids.fluids.len());
+
+    for ((handle, prev), (captured, fluid)) in
+      reference.fluids.iter().zip(&fluids.fluids)
+    {
+      if handle != captured || prev.len() != fluid.positions.len() {
+        return None;
+      }
+
+      let mut positions = Vec::with_capacity(prev.len());
+      let offsets = prev
+        .iter()
+        .zip(&fluid.positions)
+        .map(|(prev, pos)| {
+          let offset = quantize(&((pos - prev) / step))?;
+          positions.push(prev + dequantize(offset) * step);
+          Some(offset)
+        })
+        .collect::<Option<_>>()?;
+
+      let velocity_scale = scale(fluid.velocities.iter().map(|vel| vel.amax()));
+      let velocities = fluid
+        .velocities
+        .iter()
+        .map(|vel| quantize(&(vel / velocity_scale)))
+        .collect::<Option<_>>()?;
+
+      quantized.push(QuantizedFluid {
+        offsets,
+        velocity_scale,
+        velocities,
+        fields: QuantizedFields::new(&fluid.fields)?,
+      });
+      decoded.push((*handle, positions));
+    }
+
+    let mut boundaries = Vec::new();
+    for ((handle, prev), (captured, boundary)) in
+      reference.boundaries.iter().zip(&fluids.boundaries)
+    {
+      if handle != captured {
+        return None;
+      }
+      if *prev != boundary.positions {
+        boundaries.push((*handle, boundary.positions.clone()));
+      }
+    }
+
+    let bodies = physics
+      .bodies
+      .iter()
+      .filter(|(_, body)| !body.is_fixed())
+      .map(|(handle, body)| BodyState {
+        handle,
+        position: *body.position(),
+        linvel: *body.linvel(),
+        angvel: *body.angvel(),
+      })
+      .collect();
+
+    let reference = Reference {
+      fluids: decoded,
+      boundaries: fluids
+        .boundaries
+        .iter()
+        .map(|(handle, boundary)| (*handle, boundary.positions.clone()))
+        .collect(),
+    };
+    let delta = Self {
+      timestep_id: physics.timestep_id,
+      time: physics.time,
+      bodies,
+      boundaries,
+      forces: fluids.forces.clone(),
+      step,
+      fluids: quantized,
+    };
+    Some((delta, reference))
+  }
+
+  /// Applies the changes to the previous frame.
+  fn apply(&self, (physics, fluids): &mut Frame) {
+    physics.timestep_id = self.timestep_id;
+    physics.time = self.time;
+
+    for &BodyState { handle, position, linvel, angvel } in &self.bodies {
+      let Some(body) = physics.bodies.get_mut(handle) else { continue };
+      body.set_position(position, false);
+      body.set_linvel(linvel, false);
+      body.set_angvel(angvel, false);
+
+      for &collider in body.colliders() {
+        if let Some(collider) = physics.colliders.get_mut(collider)
+          && let Some(&local) = collider.position_wrt_parent()
+        {
+          collider.set_position(position * local);
+        }
+      }
+    }
+
+    for (handle, positions) in &self.boundaries {
+      if let Some((_, boundary)) =
+        fluids.boundaries.iter_mut().find(|(captured, _)| captured == handle)
+      {
+        boundary.positions.clone_from(positions);
+      }
+    }
+    fluids.forces.clone_from(&self.forces);
+    for ((_, fluid), quantized) in fluids.fluids.iter_mut().zip(&self.fluids) {
+      for (pos, &offset) in fluid.positions.iter_mut().zip(&quantized.offsets) {
+        *pos += dequantize(offset) * self.step;
+      }
+      for (vel, &quantized_vel) in
+        fluid.velocities.iter_mut().zip(&quantized.velocities)
+      {
+        *vel = dequantize(quantized_vel) * quantized.velocity_scale;
+      }
+      fluid.fields = quantized.fields.decode();
+    }
+  }
+}
+
+impl QuantizedFields {
+  fn new(fields: &Fields) -> Option<Self> {
+    let acceleration_scale =
+      scale(fields.accelerations.iter().map(|acc| acc.amax()));
+    let density_scale = scale(fields.densities.iter().copied());
+    let error_scale = scale(fields.density_error.iter().copied());
+    let pressure_scale = scale(fields.pressures.iter().copied());
+    let scalars = |values: &[Real], scale: Real| {
+      values.iter().map(|x| (x / scale).round() as i16).collect()
+    };
+    Some(Self {
+      acceleration_scale,
+      accelerations: fields
+        .accelerations
+        .iter()
+        .map(|acc| quantize(&(acc / acceleration_scale)))
+        .collect::<Option<_>>()?,
+      density_scale,
+      densities: scalars(&fields.densities, density_scale),
+      error_scale,
+      density_error: scalars(&fields.density_error, error_scale),
+      pressure_scale,
+      pressures: scalars(&fields.pressures, pressure_scale),
+      neighbours: fields
+        .neighbours
+        .iter()
+        .map(|&n| n.min(u16::MAX.into()) as u16)
+        .collect(),
+    })
+  }
+
+  fn decode(&self) -> Fields {
+    let scalars = |values: &[i16], scale: Real| {
+      values.iter().map(|&x| x as Real * scale).collect()
+    };
+    Fields {
+      accelerations: self
+        .accelerations
+        .iter()
+        .map(|&acc| dequantize(acc) * self.acceleration_scale)
+        .collect(),
+      densities: scalars(&self.densities, self.density_scale),
+      density_error: scalars(&self.density_error, self.error_scale),
+      pressures: scalars(&self.pressures, self.pressure_scale),
+      neighbours: self.neighbours.iter().map(|&n| n.into()).collect(),
+    }
+  }
+}
+
+/// Size of a quantization step of values whose magnitudes are at most the
+/// largest of `magnitudes`.
+fn scale(magnitudes: impl Iterator<Item = Real>) -> Real {
+  magnitudes.fold(0.0, Real::max).max(Real::EPSILON) / i16::MAX as Real
+}
+
+fn quantize(v: &Vector<Real>) -> Option<[i16; 3]> {
+  let component = |x: Real| {
+    let x = x.round();
+    (x.abs() <= i16::MAX as Real).then_some(x as i16)
+  };
+  Some([component(v.x)?, component(v.y)?, component(v.z)?])
+}
+
+fn dequantize([x, y, z]: [i16; 3]) -> Vector<Real> {
+  Vector::new(x as Real, y as Real, z as Real)
+}
+
+impl Reference {
+  fn new((_, fluids): &Frame) -> Self {
+    Self {
+      fluids: fluids
+        .fluids
+        .iter()
+        .map(|(handle, fluid)| (*handle, fluid.positions.clone()))
+        .collect(),
+      boundaries: fluids
+        .boundaries
+        .iter()
+        .map(|(handle, boundary)| (*handle, boundary.positions.clone()))
+        .collect(),
+    }
+  }
+}
+
+/// Approximate memory held by `record`, from the lengths of its buffers so
+/// that recording a frame does not serialize it.
+fn size(record: &Record) -> usize {
+  let fields = |fields: &Fields| {
+    size_of_val(&fields.accelerations[..])
+      + size_of_val(&fields.densities[..])
+      + size_of_val(&fields.density_error[..])
+      + size_of_val(&fields.pressures[..])
+      + size_of_val(&fields.neighbours[..])
+  };
+  match record {
+    Record::Key((physics, fluids)) => {
+      let particles: usize = fluids
+        .fluids
+        .iter()
+        .map(|(_, fluid)| {
+          size_of_val(&fluid.positions[..])
+            + size_of_val(&fluid.velocities[..])
+            + fields(&fluid.fields)
+        })
+        .sum();
+      let boundaries: usize = fluids
+        .boundaries
+        .iter()
+        .map(|(_, boundary)| size_of_val(&boundary.positions[..]))
+        .sum();
+      let contacts = physics.narrow_phase.contact_pairs().count();
+      size_of::<Frame>()
+        + particles
+        + boundaries
+        + size_of_val(&fluids.forces[..])
+        + physics.bodies.len() * size_of::<RigidBody>()
+        + physics.colliders.len() * size_of::<Collider>()
+        + contacts * size_of::<ContactPair>()
+    }
+    Record::Delta(delta) => {
+      let fluids: usize = delta
+        .fluids
+        .iter()
+        .map(|fluid| {
+          let quantized = &fluid.fields;
+          size_of_val(&fluid.offsets[..])
+            + size_of_val(&fluid.velocities[..])
+            + size_of_val(&quantized.accelerations[..])
+            + size_of_val(&quantized.densities[..])
+            + size_of_val(&quantized.density_error[..])
+            + size_of_val(&quantized.pressures[..])
+            + size_of_val(&quantized.neighbours[..])
+        })
+        .sum();
+      let boundaries: usize = delta
+        .boundaries
+        .iter()
+        .map(|(_, positions)| size_of_val(&positions[..]))
+        .sum();
+      size_of::<Delta>()
+        + fluids
+        + boundaries
+        + size_of_val(&delta.bodies[..])
+        + size_of_val(&delta.forces[..])
+    }
+  }
+}
+
+/// Reads only the header of a recorded [`Timeline`].
+pub fn read_header(mut reader: impl Read) -> io::Result<Header> {
+  fn array<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut buf = [0; N];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+  }
+
+  if &array::<4>(&mut reader)? != MAGIC {
+    return Err(io::Error::new(io::ErrorKind::InvalidData, "not a timeline"));
+  }
+
+  let version = u32::from_le_bytes(array(&mut reader)?);
+  if version != VERSION {
+    return Err(io::Error::new(
+      io::ErrorKind::InvalidData,
+      format!("unsupported timeline version {version}"),
+    ));
+  }
+
+  Ok(Header {
+    particle_radius: Real::from_le_bytes(array(&mut reader)?),
+    dt: Real::from_le_bytes(array(&mut reader)?),
+    frames: u64::from_le_bytes(array(&mut reader)?),
+  })
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+  use {
+    super::*,
+    crate::{
+      harness::{Capture, Fluids, Harness},
+      helper,
+      snapshot::PhysicsSnapshot,
+    },
+    salva::integrations::rapier::FluidsPipeline,
+  };
+
+  const RADIUS: Real = 0.05;
+
+  /// Frames of a block of particles drifting by a little more each step.
+  pub(in crate::stand) fn frames(count: usize) -> Vec<Frame> {
+    let mut fluids = Fluids::from_pipeline(FluidsPipeline::new(RADIUS, 2.0));
+    let block = helper::cube_fluid(4, 4, 4, RADIUS, 1000.0);
+    fluids.pipeline.liquid_world.add_fluid(block);
+    let first =
+      (PhysicsSnapshot::capture(&Harness::new_empty()), fluids.snapshot());
+
+    (0..count)
+      .map(|step| {
+        let mut frame = first.clone();
+        frame.0.timestep_id = step;
+        frame.0.time = step as Real / 60.0;
+        for (_, fluid) in &mut frame.1.fluids {
+          let particles = fluid.positions.iter_mut().zip(&mut fluid.velocities);
+          for (i, (position, velocity)) in particles.enumerate() {
+            let drift =
+              Vector::new(i as Real, 1.0, -2.0) * (step as Real * 1e-3);
+            *position += drift;
+            *velocity = drift * 60.0;
+          }
+        }
+        frame
+      })
+      .collect()
+  }
+
+  fn assert_decoded(timeline: &mut Timeline, frames: &[Frame]) {
+    let step = timeline.config().precision * RADIUS;
+    for (index, (physics, fluids)) in frames.iter().enumerate() {
+      let decoded = timeline.get(index).expect("recorded frame");
+      assert_eq!(decoded.0.timestep_id, physics.timestep_id);
+      for ((_, decoded), (_, fluid)) in
+        decoded.1.fluids.iter().zip(&fluids.fluids)
+      {
+        for (decoded, position) in
+          decoded.positions.iter().zip(&fluid.positions)
+        {
+          assert!((decoded - position).amax() <= step);
+        }
+        let max =
+          fluid.velocities.iter().map(|v| v.amax()).fold(0.0, Real::max);
+        for (decoded, velocity) in
+          decoded.velocities.iter().zip(&fluid.velocities)
+        {
+          assert!((decoded - velocity).amax() <= max / i16::MAX as Real);
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn deltas_decode_within_a_quantization_step() {
+    let frames = frames(10);
+    let mut timeline =
+      Timeline::with_config(Config { keyframe: 4, ..Config::default() });
+    for frame in &frames {
+      timeline.push(frame.clone());
+    }
+
+    assert!(timeline.is_keyframe(0) && timeline.is_keyframe(4));
+    assert!(!timeline.is_keyframe(3));
+    assert_eq!(timeline.keyframe_before(7), Some(4));
+    assert_decoded(&mut timeline, &frames);
+    // Backwards, through the keyframes rather than the cached frame.
+    assert!(timeline.get(2).is_some());
+    assert_decoded(&mut timeline, &frames);
+  }
+
+  #[test]
+  fn saved_timelines_read_back() {
+    let frames = frames(6);
+    let mut timeline = Timeline::default();
+    for frame in &frames {
+      timeline.push(frame.clone());
+    }
+
+    let mut bytes = Vec::new();
+    timeline.write(&mut bytes).unwrap();
+    let mut read = Timeline::read(&bytes[..]).unwrap();
+    assert_eq!(read.len(), frames.len());
+    assert_eq!(read.bytes(), timeline.bytes());
+    assert_decoded(&mut read, &frames);
+  }
+}
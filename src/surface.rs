@@ -0,0 +1,165 @@
+use {
+  crate::{mesh::Mesh, prelude::*, sph::CubicSpline},
+  rapier::math::{Point, Vector},
+  serde::{Deserialize, Serialize},
+  std::collections::{HashMap, HashSet},
+};
+
+type Key = [i32; 3];
+
+/// Splits a cube into six tetrahedra around its `0-7` diagonal, corner `c`
+/// lying at the offset `(c & 1, c >> 1 & 1, c >> 2 & 1)`. Neighbouring cubes
+/// split their shared faces the same way, so the surface has no cracks.
+const TETRAHEDRA: [[usize; 4]; 6] = [
+  [0, 1, 3, 7],
+  [0, 3, 2, 7],
+  [0, 2, 6, 7],
+  [0, 6, 4, 7],
+  [0, 4, 5, 7],
+  [0, 5, 1, 7],
+];
+
+/// Extracts the free surface of a fluid as a level set of its SPH colour
+/// field, with the marching tetrahedra variant of marching cubes.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Reconstruction {
+  /// Edge of the marched cells, relative to the particle radius.
+  pub cell: Real,
+  /// Radius of the smoothing kernel, relative to the particle radius.
+  pub kernel: Real,
+  /// Level of the colour field on the surface, the field being close to 1
+  /// inside a fluid at rest density.
+  pub iso: Real,
+}
+
+impl Default for Reconstruction {
+  fn default() -> Self {
+    Self { cell: 1.0, kernel: 4.0, iso: 0.5 }
+  }
+}
+
+impl Reconstruction {
+  /// The colour field sampled on the grid nodes around `points`, the node
+  /// `key` lying at `key * cell`.
+  fn field(
+    &self,
+    points: &[Point<Real>],
+    radius: Real,
+  ) -> (Real, HashMap<Key, Real>) {
+    let cell = self.cell * radius;
+    let kernel = CubicSpline::new(self.kernel * radius);
+    let volume = (2.0 * radius).powi(3);
+
+    let mut field = HashMap::new();
+    for point in points {
+      let min = (point.coords.add_scalar(-kernel.h) / cell).map(Real::floor);
+      let max = (point.coords.add_scalar(kernel.h) / cell).map(Real::ceil);
+      for i in min.x as i32..=max.x as i32 {
+        for j in min.y as i32..=max.y as i32 {
+          for k in min.z as i32..=max.z as i32 {
+            let node = Point::new(i as Real, j as Real, k as Real) * cell;
+            let value = kernel.value(na::distance(&node, point));
+            if value > 0.0 {
+              *field.entry([i, j, k]).or_default() += volume * value;
+            }
+          }
+        }
+      }
+    }
+    (cell, field)
+  }
+
+  /// The surface enclosing `points`, oriented outwards.
+  pub fn reconstruct(&self, points: &[Point<Real>], radius: Real) -> Mesh {
+    let (cell, field) = self.field(points, radius);
+    let value = |key: &Key| field.get(key).copied().unwrap_or_default();
+
+    // Every cube with a sampled corner.
+    let cubes: HashSet<Key> = field
+      .keys()
+      .flat_map(|&[x, y, z]| {
+        (0..8).map(move |c| [x - (c & 1), y - (c >> 1 & 1), z - (c >> 2 & 1)])
+      })
+      .collect();
+
+    let mut builder = Builder { cell, iso: self.iso, ..Default::default() };
+    for [x, y, z] in cubes {
+      let corners: [(Key, Real); 8] = std::array::from_fn(|c| {
+        let c = c as i32;
+        let key = [x + (c & 1), y + (c >> 1 & 1), z + (c >> 2 & 1)];
+        (key, value(&key))
+      });
+      if corners.iter().all(|&(_, v)| v > self.iso)
+        || corners.iter().all(|&(_, v)| v <= self.iso)
+      {
+        continue;
+      }
+      for tetrahedron in TETRAHEDRA {
+        builder.tetrahedron(tetrahedron.map(|c| corners[c]));
+      }
+    }
+    builder.mesh
+  }
+}
+
+#[derive(Default)]
+struct Builder {
+  cell: Real,
+  iso: Real,
+  mesh: Mesh,
+  /// Vertices already placed on the edge between two nodes.
+  edges: HashMap<(Key, Key), u32>,
+}
+
+impl Builder {
+  fn position(&self, key: &Key) -> Point<Real> {
+    Point::from(Vector::from(key.map(|x| x as Real)) * self.cell)
+  }
+
+  fn vertex(&mut self, a: (Key, Real), b: (Key, Real)) -> u32 {
+    let (a, b) = if a.0 <= b.0 { (a, b) } else { (b, a) };
+    if let Some(&index) = self.edges.get(&(a.0, b.0)) {
+      return index;
+    }
+
+    let t = ((self.iso - a.1) / (b.1 - a.1)).clamp(0.0, 1.0);
+    let (pa, pb) = (self.position(&a.0), self.position(&b.0));
+    let index = self.mesh.vertices.len() as u32;
+    self.mesh.vertices.push(pa + (pb - pa) * t);
+    self.edges.insert((a.0, b.0), index);
+    index
+  }
+
+  fn tetrahedron(&mut self, corners: [(Key, Real); 4]) {
+    let (inside, outside): (Vec<_>, Vec<_>) =
+      corners.into_iter().partition(|&(_, v)| v > self.iso);
+
+    match (inside.as_slice(), outside.as_slice()) {
+      (&[a], &[b, c, d]) | (&[b, c, d], &[a]) => {
+        let triangle = [(a, b), (a, c), (a, d)].map(|(u, v)| self.vertex(u, v));
+        self.triangle(triangle, &inside[0].0, &outside[0].0);
+      }
+      (&[a, b], &[c, d]) => {
+        let [ac, ad, bd, bc] =
+          [(a, c), (a, d), (b, d), (b, c)].map(|(u, v)| self.vertex(u, v));
+        self.triangle([ac, ad, bd], &a.0, &c.0);
+        self.triangle([ac, bd, bc], &a.0, &c.0);
+      }
+      _ => {}
+    }
+  }
+
+  /// Adds the triangle facing from the `inside` node to the `outside` one.
+  fn triangle(&mut self, [a, b, c]: [u32; 3], inside: &Key, outside: &Key) {
+    let [pa, pb, pc] = [a, b, c].map(|i| self.mesh.vertices[i as usize]);
+    let normal = (pb - pa).cross(&(pc - pa));
+    if normal.norm_squared() <= Real::EPSILON * self.cell.powi(4) {
+      return;
+    }
+    let outwards = self.position(outside) - self.position(inside);
+    let triangle =
+      if normal.dot(&outwards) >= 0.0 { [a, b, c] } else { [a, c, b] };
+    self.mesh.indices.push(triangle);
+  }
+}